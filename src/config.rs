@@ -0,0 +1,149 @@
+use std::sync::OnceLock;
+
+use indexmap::IndexMap;
+use miette::{miette, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::session::store::Sessions;
+
+static HTTP: OnceLock<Http> = OnceLock::new();
+static PROXY: OnceLock<Proxy> = OnceLock::new();
+
+/// Contents of `rally.toml`.
+#[derive(Serialize, Deserialize)]
+pub struct Config {
+    /// Character starting an escape sequence at the beginning of a line in a session.
+    #[serde(default = "default_escape_char")]
+    pub escape_char: char,
+
+    /// Headers and cookies sent by every HTTP-based session.
+    #[serde(default, skip_serializing_if = "Http::is_empty")]
+    pub http: Http,
+
+    /// Proxy every TCP-based session dials through, unless its URL says otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<Proxy>,
+
+    #[serde(flatten)]
+    pub sessions: Sessions,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            escape_char: default_escape_char(),
+            http: Http::default(),
+            proxy: None,
+            sessions: Sessions::default(),
+        }
+    }
+}
+
+const fn default_escape_char() -> char {
+    '~'
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Http {
+    #[serde(default)]
+    pub headers: IndexMap<String, String>,
+
+    #[serde(default)]
+    pub cookies: IndexMap<String, String>,
+}
+
+impl Http {
+    /// Use these as the defaults for sessions connected from now on.
+    pub fn install(&self) {
+        HTTP.set(self.clone()).ok();
+    }
+
+    /// The installed defaults, overridden by `header=Name:value` and `cookie=name=value`
+    /// query parameters in `url`.
+    pub fn from_url(url: &Url) -> Self {
+        let mut http = HTTP.get().cloned().unwrap_or_default();
+        for (key, value) in url.query_pairs() {
+            let (map, separator) = match &*key {
+                "header" => (&mut http.headers, ':'),
+                "cookie" => (&mut http.cookies, '='),
+                _ => continue,
+            };
+            if let Some((name, value)) = value.split_once(separator) {
+                map.insert(name.trim().to_string(), value.trim().to_string());
+            }
+        }
+        http
+    }
+
+    /// The cookies joined into a `Cookie` header value.
+    pub fn cookie_header(&self) -> Option<String> {
+        if self.cookies.is_empty() {
+            return None;
+        }
+
+        let cookies: Vec<_> = self
+            .cookies
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        Some(cookies.join("; "))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.headers.is_empty() && self.cookies.is_empty()
+    }
+}
+
+/// A SOCKS5 or HTTP CONNECT proxy, as `socks5://host:port`, `socks5h://host:port` (resolving
+/// names on the proxy) or `http://host:port`, with optional credentials.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(try_from = "Url", into = "Url")]
+pub struct Proxy {
+    pub url: Url,
+}
+
+impl Proxy {
+    /// Use this for sessions connected from now on.
+    pub fn install(&self) {
+        PROXY.set(self.clone()).ok();
+    }
+
+    /// The installed proxy, overridden by a `proxy` query parameter in `url`, where
+    /// `proxy=none` connects directly.
+    pub fn from_url(url: &Url) -> Result<Option<Self>> {
+        let Some((_, value)) = url.query_pairs().find(|(k, _)| k == "proxy") else {
+            return Ok(PROXY.get().cloned());
+        };
+        if value == "none" {
+            return Ok(None);
+        }
+
+        let url = Url::parse(&value).map_err(|e| miette!("Invalid proxy `{}`: {}.", value, e))?;
+        Self::try_from(url).map(Some).map_err(|e| miette!(e))
+    }
+
+    pub fn port(&self) -> u16 {
+        self.url.port_or_known_default().unwrap_or(1080)
+    }
+}
+
+impl TryFrom<Url> for Proxy {
+    type Error = String;
+
+    fn try_from(url: Url) -> Result<Self, Self::Error> {
+        if !matches!(url.scheme(), "socks5" | "socks5h" | "http") {
+            return Err(format!("Proxy scheme `{}` is not supported.", url.scheme()));
+        }
+        if url.host_str().is_none() {
+            return Err(format!("No host provided for proxy `{url}`."));
+        }
+        Ok(Self { url })
+    }
+}
+
+impl From<Proxy> for Url {
+    fn from(proxy: Proxy) -> Self {
+        proxy.url
+    }
+}
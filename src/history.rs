@@ -1,11 +1,45 @@
 use std::{fs::create_dir_all, path::PathBuf};
 
 use directories::ProjectDirs;
+use url::Url;
 
-pub fn get_history_path(name: &str) -> Option<PathBuf> {
+pub fn get_data_dir() -> Option<PathBuf> {
     let dirs = ProjectDirs::from("", "", env!("CARGO_PKG_NAME"))?;
     let dir = dirs.data_dir();
     create_dir_all(dir).ok();
-    let dir = dir.join(format!("{name}_history"));
+    Some(dir.to_path_buf())
+}
+
+pub fn get_history_path(name: &str) -> Option<PathBuf> {
+    let dir = get_data_dir()?.join(format!("{name}_history"));
     Some(dir)
 }
+
+pub fn get_known_hosts_path() -> Option<PathBuf> {
+    get_data_dir().map(|dir| dir.join("known_hosts"))
+}
+
+pub fn get_transcript_dir() -> Option<PathBuf> {
+    let dir = get_data_dir()?.join("transcripts");
+    create_dir_all(&dir).ok();
+    Some(dir)
+}
+
+/// Directory for files taken from the target at `url`, relative to the working directory.
+pub fn get_loot_dir(url: &Url) -> PathBuf {
+    let dir = PathBuf::from("loot").join(target_name(url));
+    create_dir_all(&dir).ok();
+    dir
+}
+
+/// A file name telling the target at `url` apart from others, like `user@host_22` or
+/// `unix_-tmp-sock`.
+pub fn target_name(url: &Url) -> String {
+    let name = match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) if url.username().is_empty() => format!("{host}_{port}"),
+        (Some(host), Some(port)) => format!("{}@{host}_{port}", url.username()),
+        // Local programs and sockets are told apart by their path.
+        (host, _) => format!("{}_{}", url.scheme(), host.unwrap_or_default()),
+    };
+    format!("{name}{}", url.path().trim_end_matches('/')).replace(['/', '\\', ':', '[', ']'], "-")
+}
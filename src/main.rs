@@ -1,9 +1,11 @@
 mod app;
+mod config;
 mod history;
 mod repl;
 mod session;
 mod style;
 mod termcraft;
+mod terminal;
 
 use app::App;
 use miette::Result;
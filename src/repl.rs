@@ -1,12 +1,11 @@
 use async_trait::async_trait;
 use clap::{
-    command,
     error::{ContextKind, ErrorKind},
     Parser, Subcommand,
 };
 use miette::{miette, IntoDiagnostic, Result};
 use owo_colors::OwoColorize;
-use rustyline::{Config, DefaultEditor, EditMode};
+use rustyline::{history::DefaultHistory, CompletionType, Config, EditMode, Editor, Helper};
 
 use crate::history::get_history_path;
 
@@ -17,9 +16,14 @@ pub trait Repl {
     fn prompt(&self) -> &str;
     async fn respond(&mut self, command: Self::Commands) -> Result<bool>;
 
+    /// Read a line of input, with completion if the REPL offers any.
+    fn read_input(&self) -> Result<String> {
+        read_line(self.prompt())
+    }
+
     async fn start(&mut self) -> Result<()> {
         loop {
-            let line = read_line(self.prompt())?;
+            let line = self.read_input()?;
             if line.is_empty() {
                 continue;
             }
@@ -36,6 +40,12 @@ pub trait Repl {
         Ok(())
     }
 
+    /// Read and handle a single command.
+    async fn read_command(&mut self) -> Result<bool> {
+        let line = self.read_input()?;
+        self.handle_command(&line).await
+    }
+
     async fn handle_command(&mut self, input: &str) -> Result<bool> {
         let input = input.trim();
         let args = shlex::split(input).ok_or_else(|| miette!("Invalid quoting."))?;
@@ -88,10 +98,19 @@ struct Cli<T: Subcommand> {
 }
 
 pub fn read_line(prompt: &str) -> Result<String> {
+    read_line_with(prompt, ())
+}
+
+/// Read a line, completing it with `helper`.
+pub fn read_line_with<H: Helper>(prompt: &str, helper: H) -> Result<String> {
     let history_res = get_history_path(prompt);
 
-    let config = Config::builder().edit_mode(EditMode::Vi).build();
-    let mut rl = DefaultEditor::with_config(config).into_diagnostic()?;
+    let config = Config::builder()
+        .edit_mode(EditMode::Vi)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut rl = Editor::<H, DefaultHistory>::with_config(config).into_diagnostic()?;
+    rl.set_helper(Some(helper));
 
     if let Some(history_path) = &history_res {
         rl.load_history(history_path).ok();
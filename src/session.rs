@@ -1,17 +1,36 @@
+pub mod escape;
+pub mod forward;
 pub mod impls;
+pub mod listener;
+pub mod output;
+pub mod recording;
 pub mod scheme;
 pub mod store;
 pub mod serde;
+pub mod shell;
+pub mod transcript;
+
+use std::io::IsTerminal;
 
 use async_trait::async_trait;
-use miette::{Context, IntoDiagnostic, Result};
+use crossterm::terminal;
+use escape::{Action, Escape};
+use forward::{Forward, Kind, Spec};
+use impls::ssh;
+use miette::{bail, miette, IntoDiagnostic, Result};
+use output::Output;
+use russh_sftp::client::SftpSession;
 use scheme::Scheme;
 use ::serde::{Deserialize, Serialize};
 use store::StoredSession;
-use tokio::sync::mpsc;
+use tokio::{
+    io::{self, AsyncReadExt, AsyncWriteExt},
+    select,
+    signal::unix::{signal, SignalKind},
+};
 use url::Url;
 
-use crate::{repl::Repl, termcraft::Termcraft};
+use crate::{repl::Repl, termcraft::Termcraft, terminal::{RawMode, SignalForwarding}};
 
 #[async_trait]
 pub trait Session {
@@ -19,7 +38,8 @@ pub trait Session {
     where
         Self: Sized;
 
-    async fn read(&mut self) -> Result<Option<Box<[u8]>>>;
+    fn url(&self) -> &Url;
+    fn output(&self) -> &Output;
     async fn is_connected(&mut self) -> bool;
     async fn reconnect(&mut self) -> Result<()>;
 
@@ -27,37 +47,221 @@ pub trait Session {
 
     async fn close(&mut self);
 
-    async fn start(&mut self) -> Result<()> {
-        let (tx, mut rx) = mpsc::channel(10);
-        let mut termcraft = Termcraft::new(tx);
+    /// Run `command` on the remote and return its output.
+    async fn exec(&mut self, command: &str) -> Result<Vec<u8>> {
+        shell::exec(self, command).await
+    }
+
+    /// Open an SFTP session alongside the shell, if the backend supports it.
+    async fn sftp(&mut self) -> Result<Option<SftpSession>> {
+        Ok(None)
+    }
+
+    /// The SSH connection other sessions can use as a jump host, if this is one.
+    fn ssh_connection(&self) -> Option<ssh::Connection> {
+        None
+    }
+
+    /// Forward a port through the session, returning the new forward and its ID.
+    async fn forward(&mut self, _kind: Kind, _spec: Spec) -> Result<(String, Forward)> {
+        bail!("This session doesn't support port forwarding.")
+    }
+
+    /// The session's active forwards, by ID.
+    fn forwards(&self) -> Vec<(String, Forward)> {
+        Vec::new()
+    }
+
+    /// Stop the forward with `id`, returning whether the session had it.
+    async fn stop_forward(&mut self, _id: &str) -> bool {
+        false
+    }
+
+    /// Switch between showing received data as-is and as a hexdump.
+    fn set_hexdump(&mut self, _enabled: bool) -> Result<()> {
+        bail!("This session has no hexdump mode.")
+    }
+
+    /// Backend-specific properties of the connection, shown by `sessions info`.
+    fn details(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    /// Whether the remote shell runs in a pty, in which case the local terminal is put
+    /// into raw mode while attached.
+    fn has_pty(&self) -> bool {
+        false
+    }
+
+    /// Whether `#upgrade` can give the remote shell a pty, tracked through `set_pty`.
+    fn can_upgrade(&self) -> bool {
+        false
+    }
+
+    /// Record that the remote shell has been upgraded to a pty.
+    fn set_pty(&mut self) {}
+
+    /// Propagate a local terminal resize to the remote pty.
+    ///
+    /// Upgraded shells have no out-of-band channel for this, so `stty` is sent instead.
+    async fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        if self.has_pty() {
+            let command = format!("stty rows {rows} cols {cols}\n");
+            self.send(command.as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    /// Forward a control key to the remote.
+    async fn control(&mut self, key: Control) -> Result<()> {
+        self.send(&[key.byte()]).await
+    }
+
+    /// Attach the local terminal until the session closes or is backgrounded.
+    ///
+    /// Input is forwarded as-is, except for escape sequences started by `escape_char`
+    /// at the beginning of a line.
+    async fn start(&mut self, escape_char: u8) -> Result<()> {
+        let mut stdin = io::stdin();
+        let mut stdout = io::stdout();
+        let mut buf = vec![0; 1024];
+        let mut escape = Escape::new(escape_char);
+        let mut window_change = signal(SignalKind::window_change()).into_diagnostic()?;
+        // Outside raw mode the keys are turned into signals for rally itself.
+        let mut interrupt = signal(SignalKind::interrupt()).into_diagnostic()?;
+        let mut suspend = signal(SignalKind::from_raw(libc::SIGTSTP)).into_diagnostic()?;
+        let mut forwarding = SignalForwarding::new()?;
+        let mut raw_mode = RawMode::default();
 
         loop {
-            let res = self.read().await?;
-            let Some(input) = res else {
+            // Checked every time since negotiation can switch echo on after attaching.
+            if self.has_pty() {
+                raw_mode.resume()?;
+            } else {
+                raw_mode.suspend()?;
+            }
+
+            // Checked first so output pushed right before closing is still shown.
+            let is_closed = self.output().is_closed();
+            let data = self.output().take_unread();
+            if !data.is_empty() {
+                stdout.write_all(&data).await.into_diagnostic()?;
+                stdout.flush().await.into_diagnostic()?;
+            }
+
+            if is_closed {
                 break Ok(());
-            };
+            }
 
-            if input.trim_ascii().starts_with(b"#") {
-                let input = String::from_utf8(input[1..].to_vec())
-                    .into_diagnostic()
-                    .wrap_err("Failed to parse command.")?;
+            let input = select! {
+                r = stdin.read(&mut buf) => match r {
+                    Ok(0) if std::io::stdin().is_terminal() => {
+                        self.control(Control::Eof).await?;
+                        continue;
+                    }
+                    Ok(0) => break Ok(()),
+                    Ok(n) => &buf[..n],
+                    Err(e) => break Err(miette!(e)),
+                },
+                () = self.output().changed() => continue,
+                Some(()) = window_change.recv() => {
+                    if let Ok((cols, rows)) = terminal::size() {
+                        self.resize(cols, rows).await?;
+                    }
+                    continue;
+                }
+                Some(()) = interrupt.recv() => {
+                    self.control(Control::Interrupt).await?;
+                    continue;
+                }
+                Some(()) = suspend.recv() => {
+                    self.control(Control::Suspend).await?;
+                    continue;
+                }
+            };
 
-                if termcraft.handle_command(&input).await? {
-                    break Ok(());
+            let (data, action) = escape.feed(input);
+            self.output().record_input(&data);
+            let mut data = &data[..];
+            while !data.is_empty() {
+                match data.iter().position(|&b| Control::from_byte(b).is_some()) {
+                    Some(0) => {
+                        let key = Control::from_byte(data[0]).unwrap();
+                        self.control(key).await?;
+                        data = &data[1..];
+                    }
+                    Some(i) => {
+                        self.send(&data[..i]).await?;
+                        data = &data[i..];
+                    }
+                    None => {
+                        self.send(data).await?;
+                        break;
+                    }
                 }
+            }
 
-                if let Some(data) = rx.recv().await.flatten() {
-                    self.send(&data).await?;
+            match action {
+                None => {}
+                Some(Action::Background) => break Ok(()),
+                Some(Action::Help) => {
+                    let help = escape.help();
+                    stdout.write_all(help.as_bytes()).await.into_diagnostic()?;
+                    stdout.flush().await.into_diagnostic()?;
+                }
+                Some(Action::Prompt(command)) => {
+                    raw_mode.suspend()?;
+                    forwarding.restore()?;
+                    println!();
+                    let mut termcraft = Termcraft::new(self);
+                    let res = match command {
+                        Some(command) => termcraft.handle_command(&command).await,
+                        None => termcraft.read_command().await,
+                    };
+                    match res {
+                        Ok(true) => break Ok(()),
+                        Ok(false) => {}
+                        Err(e) => eprintln!("{e:?}"),
+                    }
+                    forwarding.forward()?;
                 }
-            } else {
-                self.send(&input).await?;
             }
         }
     }
 }
 
+/// Control keys that act on the remote's foreground process.
+#[derive(Clone, Copy)]
+pub enum Control {
+    Interrupt,
+    Suspend,
+    Eof,
+}
+
+impl Control {
+    pub const fn from_byte(b: u8) -> Option<Self> {
+        match b {
+            0x03 => Some(Self::Interrupt),
+            0x1a => Some(Self::Suspend),
+            0x04 => Some(Self::Eof),
+            _ => None,
+        }
+    }
+
+    pub const fn byte(self) -> u8 {
+        match self {
+            Self::Interrupt => 0x03,
+            Self::Suspend => 0x1a,
+            Self::Eof => 0x04,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ConnectionInfo {
     pub url: Url,
     pub scheme: Scheme,
+    /// Accepted by a listener, so the URL is the peer's address and can't be dialled.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_inbound: bool,
 }
@@ -0,0 +1,82 @@
+/// What an escape sequence asks the session loop to do.
+pub enum Action {
+    Background,
+    /// Open the termcraft prompt, or run the command typed on the same line.
+    Prompt(Option<String>),
+    Help,
+}
+
+/// OpenSSH-style escape sequences: the escape character typed at the start of a line
+/// followed by a command character.
+pub struct Escape {
+    escape_char: u8,
+    is_line_start: bool,
+    is_pending: bool,
+}
+
+impl Escape {
+    pub const fn new(escape_char: u8) -> Self {
+        Self {
+            escape_char,
+            is_line_start: true,
+            is_pending: false,
+        }
+    }
+
+    pub fn help(&self) -> String {
+        let c = char::from(self.escape_char);
+        format!(
+            "Supported escape sequences:\r\n \
+             {c}.  - background the session\r\n \
+             {c}C  - open the termcraft prompt\r\n \
+             {c}?  - this message\r\n \
+             {c}{c}  - send the escape character\r\n\
+             (Escape sequences are only recognized after a newline.)\r\n"
+        )
+    }
+
+    /// Split `input` into the bytes to forward and the first escape action, if any.
+    ///
+    /// Input after an action is dropped.
+    pub fn feed(&mut self, input: &[u8]) -> (Vec<u8>, Option<Action>) {
+        let mut data = Vec::with_capacity(input.len());
+
+        for (i, &b) in input.iter().enumerate() {
+            if self.is_pending {
+                self.is_pending = false;
+                let action = match b {
+                    b'.' => Action::Background,
+                    b'C' | b'#' => Action::Prompt(command(&input[i + 1..])),
+                    b'?' => Action::Help,
+                    _ => {
+                        if b != self.escape_char {
+                            data.push(self.escape_char);
+                        }
+                        data.push(b);
+                        self.is_line_start = b == b'\r' || b == b'\n';
+                        continue;
+                    }
+                };
+                self.is_line_start = true;
+                return (data, Some(action));
+            }
+
+            if self.is_line_start && b == self.escape_char {
+                self.is_pending = true;
+                continue;
+            }
+
+            data.push(b);
+            self.is_line_start = b == b'\r' || b == b'\n';
+        }
+
+        (data, None)
+    }
+}
+
+/// A command typed right after the escape, as happens when the terminal is line-buffered.
+fn command(rest: &[u8]) -> Option<String> {
+    let line = rest.split(|&b| b == b'\r' || b == b'\n').next()?;
+    let line = String::from_utf8_lossy(line).trim().to_string();
+    (!line.is_empty()).then_some(line)
+}
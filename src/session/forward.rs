@@ -0,0 +1,139 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use clap::ValueEnum;
+use miette::{bail, miette, Result};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A new forward ID, unique across sessions.
+pub fn next_id() -> String {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed).to_string()
+}
+
+/// Which end of the session listens, and where connections go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Kind {
+    /// Listen locally and connect out from the remote.
+    #[value(name = "L", aliases = ["l", "local"])]
+    Local,
+
+    /// Listen on the remote and connect out from here.
+    #[value(name = "R", aliases = ["r", "remote"])]
+    Remote,
+
+    /// Listen locally as a SOCKS5 proxy, connecting out from the remote.
+    #[value(name = "D", aliases = ["d", "dynamic", "socks"])]
+    Dynamic,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local => write!(f, "Local"),
+            Self::Remote => write!(f, "Remote"),
+            Self::Dynamic => write!(f, "Dynamic"),
+        }
+    }
+}
+
+/// Where a forward listens and where it connects to, written as for `ssh -L`, `ssh -R`
+/// and `ssh -D`.
+#[derive(Debug, Clone)]
+pub struct Spec {
+    pub bind_host: String,
+    pub bind_port: u16,
+    /// Where connections go, or `None` for SOCKS clients to choose.
+    pub target: Option<(String, u16)>,
+}
+
+impl Spec {
+    /// Parse `[bind_address:]port:host:hostport`, or `[bind_address:]port` for dynamic
+    /// forwards.
+    pub fn parse(kind: Kind, s: &str) -> Result<Self> {
+        let fields = split(s).ok_or_else(|| miette!("Unbalanced brackets in `{}`.", s))?;
+        let (bind, target) = match (kind, fields.len()) {
+            (Kind::Dynamic, 1 | 2) => (&fields[..], None),
+            (Kind::Dynamic, _) => bail!("Expected `[bind_address:]port`, got `{}`.", s),
+            (_, 3 | 4) => {
+                let (bind, target) = fields.split_at(fields.len() - 2);
+                (bind, Some(target))
+            }
+            _ => bail!("Expected `[bind_address:]port:host:hostport`, got `{}`.", s),
+        };
+
+        let (bind_host, bind_port) = match bind {
+            [port] => ("127.0.0.1".to_string(), port),
+            [host, port] => (host.clone(), port),
+            _ => unreachable!(),
+        };
+        let target = match target {
+            Some([host, port]) => Some((host.clone(), parse_port(port)?)),
+            _ => None,
+        };
+
+        Ok(Self {
+            bind_host,
+            bind_port: parse_port(bind_port)?,
+            target,
+        })
+    }
+
+    pub fn bind(&self) -> String {
+        join(&self.bind_host, self.bind_port)
+    }
+
+    pub fn target(&self) -> String {
+        match &self.target {
+            Some((host, port)) => join(host, *port),
+            None => "SOCKS5".to_string(),
+        }
+    }
+}
+
+fn parse_port(s: &str) -> Result<u16> {
+    s.parse().map_err(|_| miette!("Invalid port `{}`.", s))
+}
+
+/// Split `s` on colons outside of brackets, unwrapping bracketed IPv6 addresses.
+fn split(s: &str) -> Option<Vec<String>> {
+    let mut fields = vec![String::new()];
+    let mut in_brackets = false;
+    for c in s.chars() {
+        match c {
+            '[' if !in_brackets => in_brackets = true,
+            ']' if in_brackets => in_brackets = false,
+            ':' if !in_brackets => fields.push(String::new()),
+            _ => fields.last_mut()?.push(c),
+        }
+    }
+    (!in_brackets).then_some(fields)
+}
+
+fn join(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+/// An active forward, as listed by `forwards list`.
+#[derive(Debug, Clone)]
+pub struct Forward {
+    pub kind: Kind,
+    pub spec: Spec,
+}
+
+impl fmt::Display for Forward {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            Kind::Local => 'L',
+            Kind::Remote => 'R',
+            Kind::Dynamic => 'D',
+        };
+        write!(f, "{kind} {} → {}", self.spec.bind(), self.spec.target())
+    }
+}
@@ -1 +1,31 @@
+pub mod http;
+pub mod process;
+pub mod proxy;
 pub mod ssh;
+pub mod tcp;
+pub mod telnet;
+pub mod tls;
+pub mod udp;
+pub mod ws;
+
+use url::Url;
+
+/// `url` without the query parameters in `params` and the proxy, which are meant for rally
+/// itself, and without the TLS options if the scheme uses TLS.
+fn strip_query(url: &Url, params: &[&str]) -> Url {
+    let is_tls = matches!(url.scheme(), "https" | "wss");
+    let mut stripped = url.clone();
+    let pairs: Vec<_> = url
+        .query_pairs()
+        .filter(|(k, _)| {
+            !(params.contains(&&**k) || k == proxy::PARAM || is_tls && tls::PARAMS.contains(&&**k))
+        })
+        .collect();
+
+    if pairs.is_empty() {
+        stripped.set_query(None);
+    } else {
+        stripped.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    stripped
+}
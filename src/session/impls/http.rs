@@ -0,0 +1,241 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use miette::{bail, miette, IntoDiagnostic, Result};
+use regex::bytes::Regex;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, COOKIE},
+    Client, Method,
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+use url::Url;
+
+use super::{strip_query, tls};
+use crate::{
+    config::{Http as HttpConfig, Proxy},
+    session::{output::Output, store::StoredSession, ConnectionInfo, Control, Session},
+};
+
+/// Query parameters consumed by rally rather than passed on to the webshell.
+const PARAMS: [&str; 5] = ["method", "param", "extract", "header", "cookie"];
+
+/// A webshell, run with one request per line of input.
+///
+/// The URL query configures the request: `method` (default `POST`), `param` naming the
+/// parameter carrying the command (default `cmd`), `extract` with a regex whose first group
+/// (or whole match) is taken from the response, and `header`s and `cookie`s as for `ws://`.
+pub struct Http {
+    url: Url,
+    shell: Arc<Webshell>,
+    lines: mpsc::UnboundedSender<String>,
+    worker: JoinHandle<()>,
+    output: Output,
+    /// Input not yet terminated by a newline.
+    pending: Vec<u8>,
+}
+
+struct Webshell {
+    client: Client,
+    url: Url,
+    method: Method,
+    param: String,
+    extract: Option<Regex>,
+}
+
+impl Webshell {
+    fn from_url(url: &Url) -> Result<Self> {
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.into_owned())
+        };
+
+        let method = match param("method") {
+            Some(method) => Method::from_bytes(method.to_uppercase().as_bytes())
+                .map_err(|_| miette!("Invalid method `{}`.", method))?,
+            None => Method::POST,
+        };
+        let extract = param("extract")
+            .map(|re| Regex::new(&re))
+            .transpose()
+            .into_diagnostic()?;
+
+        let config = HttpConfig::from_url(url);
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::try_from(name).into_diagnostic()?;
+            let value = HeaderValue::try_from(value).into_diagnostic()?;
+            headers.insert(name, value);
+        }
+        if let Some(cookies) = config.cookie_header() {
+            headers.insert(COOKIE, HeaderValue::try_from(cookies).into_diagnostic()?);
+        }
+
+        let mut client = Client::builder().default_headers(headers);
+        match Proxy::from_url(url)? {
+            Some(proxy) => client = client.proxy(reqwest::Proxy::all(proxy.url).into_diagnostic()?),
+            None => client = client.no_proxy(),
+        }
+        if url.scheme() == "https" {
+            client = client.use_preconfigured_tls(tls::client_config(url)?);
+        }
+
+        Ok(Self {
+            client: client.build().into_diagnostic()?,
+            url: strip_query(url, &PARAMS),
+            method,
+            param: param("param").unwrap_or_else(|| "cmd".to_string()),
+            extract,
+        })
+    }
+
+    /// Run `command`, returning the extracted part of the response.
+    async fn run(&self, command: &str) -> Result<Vec<u8>> {
+        let request = self.client.request(self.method.clone(), self.url.clone());
+        let request = if self.method == Method::GET {
+            request.query(&[(&self.param, command)])
+        } else {
+            request.form(&[(&self.param, command)])
+        };
+
+        let response = request.send().await.into_diagnostic()?;
+        let status = response.status();
+        let body = response.bytes().await.into_diagnostic()?;
+        if !status.is_success() && body.is_empty() {
+            bail!("Webshell responded with {}.", status);
+        }
+
+        let Some(extract) = &self.extract else {
+            return Ok(body.to_vec());
+        };
+        let mut out = Vec::new();
+        for captures in extract.captures_iter(&body) {
+            let m = captures.get(1).or_else(|| captures.get(0)).unwrap();
+            out.extend_from_slice(m.as_bytes());
+        }
+        Ok(out)
+    }
+}
+
+impl Http {
+    fn new(url: Url, output: Output) -> Result<Self> {
+        let shell = Arc::new(Webshell::from_url(&url)?);
+        let (lines, worker) = spawn_worker(Arc::clone(&shell), output.clone());
+
+        Ok(Self {
+            url,
+            shell,
+            lines,
+            worker,
+            output,
+            pending: Vec::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl Session for Http {
+    async fn connect(url: Url) -> Result<StoredSession> {
+        let scheme = url.scheme().parse()?;
+        let http = Self::new(url.clone(), Output::default())?;
+        let connection_info = ConnectionInfo {
+            url,
+            scheme,
+            is_inbound: false,
+        };
+
+        Ok(StoredSession {
+            connection_info,
+            session: Box::new(http),
+        })
+    }
+
+    fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
+
+    async fn is_connected(&mut self) -> bool {
+        !self.output.is_closed()
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        if !self.is_connected().await {
+            self.worker.abort();
+            let output = self.output.clone();
+            output.reopen();
+            *self = Self::new(self.url.clone(), output.clone()).inspect_err(|_| output.close())?;
+        }
+
+        Ok(())
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(data);
+        while let Some(i) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=i).collect();
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            self.lines
+                .send(line)
+                .map_err(|_| miette!("Session is closed."))?;
+        }
+        Ok(())
+    }
+
+    /// There is no foreground process to signal, so control keys just discard the line.
+    async fn control(&mut self, _key: Control) -> Result<()> {
+        self.pending.clear();
+        Ok(())
+    }
+
+    async fn exec(&mut self, command: &str) -> Result<Vec<u8>> {
+        self.shell.run(command).await
+    }
+
+    fn details(&self) -> Vec<(&'static str, String)> {
+        let mut details = vec![
+            ("Method", self.shell.method.to_string()),
+            ("Parameter", self.shell.param.clone()),
+        ];
+        if let Some(extract) = &self.shell.extract {
+            details.push(("Extract", extract.to_string()));
+        }
+        details
+    }
+
+    async fn close(&mut self) {
+        self.worker.abort();
+        self.output.close();
+    }
+}
+
+/// Run lines one at a time in the background, pushing their output.
+fn spawn_worker(
+    shell: Arc<Webshell>,
+    output: Output,
+) -> (mpsc::UnboundedSender<String>, JoinHandle<()>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+
+    let worker = tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            if line.is_empty() {
+                continue;
+            }
+
+            match shell.run(&line).await {
+                Ok(mut out) => {
+                    if out.last().is_some_and(|&b| b != b'\n') {
+                        out.push(b'\n');
+                    }
+                    output.push(&out);
+                }
+                Err(e) => output.push(format!("{e}\n").as_bytes()),
+            }
+        }
+    });
+
+    (tx, worker)
+}
@@ -0,0 +1,270 @@
+use std::{
+    fs::File,
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    process::Stdio,
+    ptr,
+};
+
+use async_trait::async_trait;
+use miette::{bail, miette, IntoDiagnostic, Result};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    process::{Child, Command},
+    task::JoinHandle,
+};
+use url::Url;
+
+use crate::session::{
+    output::Output, scheme::Scheme, store::StoredSession, ConnectionInfo, Session,
+};
+
+type Writer = Box<dyn AsyncWrite + Send + Sync + Unpin>;
+
+/// A local program, run under a pty or with plain pipes.
+pub struct Process {
+    url: Url,
+    child: Child,
+    writer: Writer,
+    reader: JoinHandle<()>,
+    /// Master side of the pty, kept for resizing.
+    pty: Option<File>,
+    /// A pipe-mode program started a pty itself, through `#upgrade`.
+    is_upgraded: bool,
+    output: Output,
+}
+
+enum Mode {
+    Pty,
+    Pipe,
+}
+
+impl Mode {
+    fn from_url(url: &Url) -> Result<Self> {
+        match url.query_pairs().find(|(k, _)| k == "mode") {
+            None => Ok(Self::Pty),
+            Some((_, mode)) => match &*mode {
+                "pty" => Ok(Self::Pty),
+                "pipe" | "pipes" => Ok(Self::Pipe),
+                _ => bail!("Unknown process mode `{}`.", mode),
+            },
+        }
+    }
+}
+
+impl Process {
+    /// Start the program for `url`, pushing what it prints to `output`.
+    async fn spawn(url: Url, output: Output) -> Result<Self> {
+        let mut command = command(&url)?;
+        command.kill_on_drop(true);
+
+        let (child, writer, reader, pty): (_, Writer, _, _) = match Mode::from_url(&url)? {
+            Mode::Pty => {
+                let (master, slave) = open_pty()?;
+                command
+                    .stdin(slave.try_clone().into_diagnostic()?)
+                    .stdout(slave.try_clone().into_diagnostic()?)
+                    .stderr(slave);
+                // SAFETY: only async-signal-safe calls are made between fork and exec.
+                unsafe {
+                    command.pre_exec(|| {
+                        if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                            return Err(io::Error::last_os_error());
+                        }
+                        Ok(())
+                    });
+                }
+                let child = command.spawn().into_diagnostic()?;
+
+                let writer = tokio::fs::File::from_std(master.try_clone().into_diagnostic()?);
+                let reader = tokio::fs::File::from_std(master.try_clone().into_diagnostic()?);
+                let reader = output.spawn_reader(reader);
+                (child, Box::new(writer), reader, Some(master))
+            }
+            Mode::Pipe => {
+                // stdout and stderr share one pipe so output stays in order.
+                let (reader, writer) = io::pipe().into_diagnostic()?;
+                command
+                    .stdin(Stdio::piped())
+                    .stdout(writer.try_clone().into_diagnostic()?)
+                    .stderr(writer);
+                let mut child = command.spawn().into_diagnostic()?;
+
+                let stdin = child
+                    .stdin
+                    .take()
+                    .ok_or_else(|| miette!("Failed to open stdin."))?;
+                let reader = tokio::fs::File::from_std(File::from(OwnedFd::from(reader)));
+                let reader = output.spawn_reader(reader);
+                (child, Box::new(stdin), reader, None)
+            }
+        };
+        // The write ends held by `command` must be closed for EOF to reach the reader.
+        drop(command);
+
+        Ok(Self {
+            url,
+            child,
+            writer,
+            reader,
+            pty,
+            is_upgraded: false,
+            output,
+        })
+    }
+}
+
+#[async_trait]
+impl Session for Process {
+    async fn connect(url: Url) -> Result<StoredSession> {
+        let process = Self::spawn(url.clone(), Output::default()).await?;
+        let connection_info = ConnectionInfo {
+            url,
+            scheme: Scheme::Proc,
+            is_inbound: false,
+        };
+
+        Ok(StoredSession {
+            connection_info,
+            session: Box::new(process),
+        })
+    }
+
+    fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
+
+    async fn is_connected(&mut self) -> bool {
+        !self.output.is_closed()
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        if !self.is_connected().await {
+            self.close().await;
+            // Keep the same output so the scrollback and any transcript carry over, closing
+            // it again if the program can't be started.
+            let output = self.output.clone();
+            output.reopen();
+            *self = Self::spawn(self.url.clone(), output.clone())
+                .await
+                .inspect_err(|_| output.close())?;
+        }
+
+        Ok(())
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        let res = async {
+            self.writer.write_all(data).await?;
+            self.writer.flush().await
+        }
+        .await
+        .into_diagnostic();
+        if res.is_err() {
+            self.output.close();
+        }
+        res
+    }
+
+    fn has_pty(&self) -> bool {
+        self.pty.is_some() || self.is_upgraded
+    }
+
+    fn can_upgrade(&self) -> bool {
+        true
+    }
+
+    fn set_pty(&mut self) {
+        self.is_upgraded = true;
+    }
+
+    async fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        if let Some(pty) = &self.pty {
+            set_size(pty, cols, rows).into_diagnostic()?;
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) {
+        self.child.start_kill().ok();
+        self.child.wait().await.ok();
+        self.reader.abort();
+        self.output.close();
+    }
+}
+
+/// Build the command for `url`.
+///
+/// `proc:///bin/sh` runs an absolute path and `proc://./vuln` one relative to the working
+/// directory. Arguments are passed as repeated `arg` query parameters.
+fn command(url: &Url) -> Result<Command> {
+    let program = match url.host_str() {
+        Some(host) => format!("{host}{}", url.path()),
+        None => url.path().to_string(),
+    };
+    if program.is_empty() || program == "/" {
+        bail!("No program provided.");
+    }
+
+    let mut command = Command::new(program);
+    command.args(
+        url.query_pairs()
+            .filter(|(k, _)| k == "arg")
+            .map(|(_, v)| v.into_owned()),
+    );
+    if let Ok(term) = std::env::var("TERM") {
+        command.env("TERM", term);
+    }
+    Ok(command)
+}
+
+/// Open a pty sized like the local terminal, returning the master and slave ends.
+fn open_pty() -> Result<(File, File)> {
+    let mut master = -1;
+    let mut slave = -1;
+    // SAFETY: both out-pointers are valid and the optional arguments are null.
+    let res = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            ptr::null_mut(),
+            ptr::null(),
+            ptr::null(),
+        )
+    };
+    if res == -1 {
+        return Err(io::Error::last_os_error()).into_diagnostic();
+    }
+
+    // SAFETY: openpty succeeded, so both descriptors are open and owned by us.
+    let (master, slave) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) };
+    // Keep other children, like the next session's, from inheriting them.
+    for pty in [&master, &slave] {
+        // SAFETY: `pty` is an open descriptor.
+        if unsafe { libc::fcntl(pty.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error()).into_diagnostic();
+        }
+    }
+    if let Ok((cols, rows)) = crossterm::terminal::size() {
+        set_size(&master, cols, rows).ok();
+    }
+    Ok((master, slave))
+}
+
+fn set_size(pty: &File, cols: u16, rows: u16) -> io::Result<()> {
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: `pty` is an open descriptor and `size` outlives the call.
+    if unsafe { libc::ioctl(pty.as_raw_fd(), libc::TIOCSWINSZ, &size) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::{io, net::IpAddr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use miette::{bail, miette, IntoDiagnostic, Result, WrapErr};
+use percent_encoding::percent_decode_str;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream},
+};
+use url::{Host, Url};
+
+use crate::config::Proxy;
+
+/// Query parameter choosing the proxy for a session.
+pub const PARAM: &str = "proxy";
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const USERNAME_PASSWORD: u8 = 2;
+const CONNECT: u8 = 1;
+const IPV4: u8 = 1;
+const DOMAIN: u8 = 3;
+const IPV6: u8 = 4;
+
+/// Longest HTTP CONNECT response header accepted from a proxy.
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// Open a TCP connection to `host:port` for the session at `url`, through its proxy if it
+/// has one.
+pub async fn connect(url: &Url, host: &str, port: u16) -> Result<TcpStream> {
+    // The URL's brackets around an IPv6 address aren't part of it.
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let Some(proxy) = Proxy::from_url(url)? else {
+        return TcpStream::connect((host, port)).await.into_diagnostic();
+    };
+
+    // Checked when the proxy was parsed.
+    let proxy_host = proxy.url.host_str().unwrap();
+    let mut stream = connect_host(proxy.url.host().unwrap(), proxy.port())
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to connect to proxy {proxy_host}"))?;
+
+    let res = match proxy.url.scheme() {
+        "http" => http_connect(&mut stream, &proxy.url, host, port).await,
+        scheme => socks5_connect(&mut stream, &proxy.url, host, port, scheme == "socks5h").await,
+    };
+    res.wrap_err_with(|| format!("Proxy {proxy_host} could not connect to {host}:{port}"))?;
+    Ok(stream)
+}
+
+async fn connect_host(host: Host<&str>, port: u16) -> io::Result<TcpStream> {
+    match host {
+        Host::Domain(domain) => TcpStream::connect((domain, port)).await,
+        Host::Ipv4(ip) => TcpStream::connect((ip, port)).await,
+        Host::Ipv6(ip) => TcpStream::connect((ip, port)).await,
+    }
+}
+
+async fn socks5_connect(
+    stream: &mut TcpStream,
+    proxy: &Url,
+    host: &str,
+    port: u16,
+    remote_dns: bool,
+) -> Result<()> {
+    let has_credentials = !proxy.username().is_empty();
+    let greeting: &[u8] = if has_credentials {
+        &[SOCKS_VERSION, 2, NO_AUTH, USERNAME_PASSWORD]
+    } else {
+        &[SOCKS_VERSION, 1, NO_AUTH]
+    };
+    stream.write_all(greeting).await.into_diagnostic()?;
+
+    let mut choice = [0; 2];
+    stream.read_exact(&mut choice).await.into_diagnostic()?;
+    match choice {
+        [SOCKS_VERSION, NO_AUTH] => {}
+        [SOCKS_VERSION, USERNAME_PASSWORD] if has_credentials => {
+            let (user, password) = credentials(proxy);
+            let mut auth = vec![1];
+            push_field(&mut auth, "Username", &user)?;
+            push_field(&mut auth, "Password", &password)?;
+            stream.write_all(&auth).await.into_diagnostic()?;
+
+            let mut status = [0; 2];
+            stream.read_exact(&mut status).await.into_diagnostic()?;
+            if status[1] != 0 {
+                bail!("Authentication failed.");
+            }
+        }
+        _ => bail!("No supported authentication method."),
+    }
+
+    let mut request = vec![SOCKS_VERSION, CONNECT, 0];
+    let ip = match host.parse::<IpAddr>() {
+        Ok(ip) => Some(ip),
+        Err(_) if remote_dns => None,
+        Err(_) => Some(
+            lookup_host((host, port))
+                .await
+                .into_diagnostic()?
+                .next()
+                .ok_or_else(|| miette!("Failed to resolve `{}`.", host))?
+                .ip(),
+        ),
+    };
+    match ip {
+        Some(IpAddr::V4(ip)) => {
+            request.push(IPV4);
+            request.extend(ip.octets());
+        }
+        Some(IpAddr::V6(ip)) => {
+            request.push(IPV6);
+            request.extend(ip.octets());
+        }
+        None => {
+            request.push(DOMAIN);
+            push_field(&mut request, "Host name", host.as_bytes())?;
+        }
+    }
+    request.extend(port.to_be_bytes());
+    stream.write_all(&request).await.into_diagnostic()?;
+
+    // The reply is followed by the proxy's bound address, which is skipped.
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).await.into_diagnostic()?;
+    if reply[1] != 0 {
+        bail!("{}.", socks5_error(reply[1]));
+    }
+    let len = match reply[3] {
+        IPV4 => 4,
+        IPV6 => 16,
+        DOMAIN => stream.read_u8().await.into_diagnostic()?.into(),
+        _ => bail!("Invalid reply."),
+    };
+    let mut bound = vec![0; len + 2];
+    stream.read_exact(&mut bound).await.into_diagnostic()?;
+    Ok(())
+}
+
+/// Append `data` with the single-byte length prefix SOCKS5 uses for variable fields.
+fn push_field(buf: &mut Vec<u8>, name: &str, data: &[u8]) -> Result<()> {
+    let len = u8::try_from(data.len()).map_err(|_| miette!("{} is over 255 bytes.", name))?;
+    buf.push(len);
+    buf.extend(data);
+    Ok(())
+}
+
+fn socks5_error(code: u8) -> &'static str {
+    match code {
+        1 => "General failure",
+        2 => "Connection not allowed by ruleset",
+        3 => "Network unreachable",
+        4 => "Host unreachable",
+        5 => "Connection refused",
+        6 => "TTL expired",
+        7 => "Command not supported",
+        8 => "Address type not supported",
+        _ => "Unknown error",
+    }
+}
+
+async fn http_connect(stream: &mut TcpStream, proxy: &Url, host: &str, port: u16) -> Result<()> {
+    let authority = if host.contains(':') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    };
+    let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    if !proxy.username().is_empty() {
+        let (user, password) = credentials(proxy);
+        let credentials = [&user[..], b":", &password[..]].concat();
+        request += &format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            STANDARD.encode(credentials)
+        );
+    }
+    request += "\r\n";
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .into_diagnostic()?;
+
+    // Read byte by byte so nothing after the headers is consumed.
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() == MAX_HEADER_SIZE {
+            bail!("Proxy response headers are over {} bytes.", MAX_HEADER_SIZE);
+        }
+        response.push(stream.read_u8().await.into_diagnostic()?);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => bail!("Proxy responded with `{}`.", status),
+    }
+}
+
+/// The percent-decoded username and password in `proxy`.
+fn credentials(proxy: &Url) -> (Vec<u8>, Vec<u8>) {
+    let decode = |s: &str| percent_decode_str(s).collect();
+    (
+        decode(proxy.username()),
+        decode(proxy.password().unwrap_or("")),
+    )
+}
@@ -0,0 +1,164 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use miette::{bail, miette, IntoDiagnostic, Result};
+use russh::{
+    client::{self, KeyboardInteractiveAuthResponse},
+    keys::{self, agent::client::AgentClient},
+};
+use url::Url;
+
+/// How to authenticate an SSH session, selected through the URL query.
+///
+/// `?identity=<path>` uses a private key, `?auth=agent` uses the running
+/// ssh-agent and `?auth=keyboard-interactive` answers the server's prompts.
+/// Otherwise the password from the URL is used. An explicit `auth` wins over `identity`.
+pub enum Auth {
+    Password,
+    PublicKey(PathBuf),
+    Agent,
+    KeyboardInteractive,
+}
+
+impl Auth {
+    pub fn from_url(url: &Url) -> Result<Self> {
+        let mut method = None;
+        let mut identity = None;
+        for (key, value) in url.query_pairs() {
+            match &*key {
+                "auth" => method = Some(value.into_owned()),
+                "identity" => identity = Some(PathBuf::from(&*value)),
+                _ => {}
+            }
+        }
+
+        Ok(match (method.as_deref(), identity) {
+            (None | Some("publickey"), Some(identity)) => Self::PublicKey(identity),
+            (Some("publickey"), None) => bail!("No identity file provided."),
+            (None, None) | (Some("password"), _) => Self::Password,
+            (Some("agent"), _) => Self::Agent,
+            (Some("keyboard-interactive" | "kbdint"), _) => Self::KeyboardInteractive,
+            (Some(method), _) => bail!("Authentication method `{}` is not supported.", method),
+        })
+    }
+
+    pub async fn authenticate<H: client::Handler>(
+        &self,
+        session: &mut client::Handle<H>,
+        url: &Url,
+    ) -> Result<()> {
+        let user = url.username();
+
+        let (method, res) = match self {
+            Self::Password => {
+                let password = url.password().unwrap_or("");
+                let res = session.authenticate_password(user, password).await;
+                ("password", res.into_diagnostic()?)
+            }
+            Self::PublicKey(path) => {
+                let key = load_key(path)?;
+                let res = session.authenticate_publickey(user, Arc::new(key)).await;
+                ("public key", res.into_diagnostic()?)
+            }
+            Self::Agent => ("agent", authenticate_agent(session, user).await?),
+            Self::KeyboardInteractive => (
+                "keyboard-interactive",
+                authenticate_keyboard_interactive(session, url).await?,
+            ),
+        };
+
+        if !res {
+            bail!("Authentication (with {method}) failed.");
+        }
+
+        Ok(())
+    }
+}
+
+fn load_key(path: &Path) -> Result<keys::key::KeyPair> {
+    match keys::load_secret_key(path, None) {
+        Err(keys::Error::KeyIsEncrypted) => {
+            let passphrase = rpassword::prompt_password(format!(
+                "Enter passphrase for key '{}': ",
+                path.display()
+            ))
+            .into_diagnostic()?;
+            keys::load_secret_key(path, Some(&passphrase)).into_diagnostic()
+        }
+        res => res.into_diagnostic(),
+    }
+}
+
+async fn authenticate_agent<H: client::Handler>(
+    session: &mut client::Handle<H>,
+    user: &str,
+) -> Result<bool> {
+    let mut agent = AgentClient::connect_env()
+        .await
+        .map_err(|e| miette!("Failed to connect to ssh-agent: {e}"))?;
+    let identities = agent.request_identities().await.into_diagnostic()?;
+
+    for key in identities {
+        let (returned, res) = session.authenticate_future(user, key, agent).await;
+        agent = returned;
+        if res.into_diagnostic()? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+async fn authenticate_keyboard_interactive<H: client::Handler>(
+    session: &mut client::Handle<H>,
+    url: &Url,
+) -> Result<bool> {
+    let mut password = url.password().map(ToString::to_string);
+    let mut res = session
+        .authenticate_keyboard_interactive_start(url.username(), None)
+        .await
+        .into_diagnostic()?;
+
+    loop {
+        let prompts = match res {
+            KeyboardInteractiveAuthResponse::Success => break Ok(true),
+            KeyboardInteractiveAuthResponse::Failure => break Ok(false),
+            KeyboardInteractiveAuthResponse::InfoRequest {
+                name,
+                instructions,
+                prompts,
+            } => {
+                for line in [name, instructions] {
+                    if !line.is_empty() {
+                        println!("{line}");
+                    }
+                }
+                prompts
+            }
+        };
+
+        let mut responses = Vec::with_capacity(prompts.len());
+        for prompt in prompts {
+            let response = if prompt.echo {
+                print!("{}", prompt.prompt);
+                std::io::stdout().flush().into_diagnostic()?;
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line).into_diagnostic()?;
+                line.trim_end_matches(['\r', '\n']).to_string()
+            } else if let Some(password) = password.take() {
+                password
+            } else {
+                rpassword::prompt_password(&prompt.prompt).into_diagnostic()?
+            };
+            responses.push(response);
+        }
+
+        res = session
+            .authenticate_keyboard_interactive_respond(responses)
+            .await
+            .into_diagnostic()?;
+    }
+}
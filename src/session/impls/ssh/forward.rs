@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use miette::{miette, IntoDiagnostic, Result};
+use russh::{client, Channel};
+use tokio::{
+    io::copy_bidirectional,
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use super::{socks, Connection};
+use crate::session::forward::{Forward, Kind, Spec};
+
+/// Where to connect for each remote forward, by the port the server listens on.
+pub type Targets = Arc<Mutex<HashMap<u32, (String, u16)>>>;
+
+/// A forward running on a session.
+pub struct Active {
+    pub forward: Forward,
+    /// Accepts connections for local forwards; remote ones are accepted by the server.
+    task: Option<JoinHandle<()>>,
+}
+
+impl Active {
+    /// Listen on `spec`'s bind address, opening a `direct-tcpip` channel per connection.
+    ///
+    /// Without a target, each connection is a SOCKS5 client naming its own.
+    pub async fn local(connection: Connection, kind: Kind, mut spec: Spec) -> Result<Self> {
+        let listener = TcpListener::bind((spec.bind_host.as_str(), spec.bind_port))
+            .await
+            .into_diagnostic()?;
+        spec.bind_port = listener.local_addr().into_diagnostic()?.port();
+
+        let target = spec.target.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let connection = connection.clone();
+                let target = target.clone();
+                tokio::spawn(async move {
+                    let Some((host, port)) = target else {
+                        socks::serve(stream, connection).await.ok();
+                        return;
+                    };
+                    if let Ok(mut channel) = connection.direct_tcpip(&host, port).await {
+                        copy_bidirectional(&mut stream, &mut channel).await.ok();
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            forward: Forward { kind, spec },
+            task: Some(task),
+        })
+    }
+
+    /// Ask the server to listen on `spec`'s bind address.
+    pub async fn remote(
+        connection: &Connection,
+        targets: &Targets,
+        mut spec: Spec,
+    ) -> Result<Self> {
+        request(connection, targets, &mut spec).await?;
+
+        Ok(Self {
+            forward: Forward {
+                kind: Kind::Remote,
+                spec,
+            },
+            task: None,
+        })
+    }
+
+    /// Set the forward up again on a new connection.
+    pub async fn restore(&mut self, connection: &Connection, targets: &Targets) -> Result<()> {
+        match self.forward.kind {
+            // Local forwards open their channels through the shared connection as needed.
+            Kind::Local | Kind::Dynamic => Ok(()),
+            Kind::Remote => request(connection, targets, &mut self.forward.spec).await,
+        }
+    }
+
+    pub async fn stop(self, connection: &Connection, targets: &Targets) {
+        if let Kind::Remote = self.forward.kind {
+            let spec = &self.forward.spec;
+            targets.lock().unwrap().remove(&spec.bind_port.into());
+            connection
+                .read()
+                .await
+                .cancel_tcpip_forward(&spec.bind_host, spec.bind_port.into())
+                .await
+                .ok();
+        }
+    }
+}
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+async fn request(connection: &Connection, targets: &Targets, spec: &mut Spec) -> Result<()> {
+    let target = spec
+        .target
+        .clone()
+        .ok_or_else(|| miette!("Remote forwards need a target."))?;
+    let port = connection
+        .write()
+        .await
+        .tcpip_forward(&spec.bind_host, spec.bind_port.into())
+        .await
+        .into_diagnostic()?;
+    // The server only says which port it picked when asked for any.
+    if port != 0 {
+        spec.bind_port = port as u16;
+    }
+
+    targets
+        .lock()
+        .unwrap()
+        .insert(spec.bind_port.into(), target);
+    Ok(())
+}
+
+/// Connect a channel the server opened for a remote forward to its target.
+pub fn accept(targets: &Targets, channel: Channel<client::Msg>, port: u32) {
+    let Some((host, port)) = targets.lock().unwrap().get(&port).cloned() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        if let Ok(mut stream) = TcpStream::connect((host, port)).await {
+            copy_bidirectional(&mut channel.into_stream(), &mut stream)
+                .await
+                .ok();
+        }
+    });
+}
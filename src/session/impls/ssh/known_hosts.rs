@@ -0,0 +1,106 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use miette::{miette, Report};
+use owo_colors::OwoColorize;
+use russh::keys::{self, key::PublicKey};
+
+use crate::history::get_known_hosts_path;
+
+/// A server presented a different key than the one recorded for it.
+pub struct Mismatch {
+    pub expected: PublicKey,
+    pub actual: PublicKey,
+}
+
+impl Mismatch {
+    pub fn report(&self, host: &str, port: u16) -> Report {
+        miette!(
+            help = format!(
+                "Expected {} {}\nReceived {} {}\nIf the host was rebuilt, accept the new key with `sessions trust <id>`.",
+                self.expected.name(),
+                fingerprint(&self.expected),
+                self.actual.name(),
+                fingerprint(&self.actual).red(),
+            ),
+            "Host key for {}:{} has changed!",
+            host,
+            port
+        )
+    }
+}
+
+/// Check `key` against rally's known_hosts, learning it on first use.
+///
+/// Returns the recorded key if `key` does not match it.
+pub fn verify(host: &str, port: u16, key: &PublicKey) -> Result<Option<PublicKey>, keys::Error> {
+    let path = path()?;
+
+    match keys::check_known_hosts_path(host, port, key, &path) {
+        Ok(true) => Ok(None),
+        Ok(false) => {
+            keys::learn_known_hosts_path(host, port, key, &path)?;
+            println!(
+                "Permanently added '{}:{}' ({}) to the list of known hosts.",
+                host,
+                port,
+                key.name()
+            );
+            Ok(None)
+        }
+        Err(keys::Error::KeyChanged { line }) => {
+            let expected = keys::known_host_keys_path(host, port, &path)?
+                .into_iter()
+                .find_map(|(l, expected)| (l == line).then_some(expected))
+                .ok_or(keys::Error::KeyChanged { line })?;
+            Ok(Some(expected))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Replace every key recorded for `host` with `key`.
+pub fn trust(host: &str, port: u16, key: &PublicKey) -> Result<(), keys::Error> {
+    let path = path()?;
+
+    let stale: Vec<_> = keys::known_host_keys_path(host, port, &path)?
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect();
+
+    if !stale.is_empty() {
+        let file = fs::File::open(&path)?;
+        let mut kept = Vec::new();
+        // Line numbers reported by russh skip comments.
+        let mut n = 0;
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.starts_with('#') {
+                n += 1;
+                if stale.contains(&n) {
+                    continue;
+                }
+            }
+            writeln!(kept, "{line}")?;
+        }
+        fs::write(&path, kept)?;
+    }
+
+    keys::learn_known_hosts_path(host, port, key, &path)
+}
+
+fn path() -> Result<PathBuf, keys::Error> {
+    get_known_hosts_path().ok_or_else(|| {
+        keys::Error::IO(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No data directory found for known_hosts.",
+        ))
+    })
+}
+
+fn fingerprint(key: &PublicKey) -> String {
+    format!("SHA256:{}", key.fingerprint())
+}
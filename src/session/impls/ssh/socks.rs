@@ -0,0 +1,80 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use tokio::{
+    io::{self, copy_bidirectional, AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::Connection;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+const CONNECT: u8 = 1;
+
+const IPV4: u8 = 1;
+const DOMAIN: u8 = 3;
+const IPV6: u8 = 4;
+
+const SUCCEEDED: u8 = 0;
+const HOST_UNREACHABLE: u8 = 4;
+const COMMAND_NOT_SUPPORTED: u8 = 7;
+const ADDRESS_TYPE_NOT_SUPPORTED: u8 = 8;
+
+/// Serve one SOCKS5 client, tunnelling its `CONNECT` through a `direct-tcpip` channel.
+pub async fn serve(mut stream: TcpStream, connection: Connection) -> io::Result<()> {
+    // Greeting: the version, then the authentication methods the client supports.
+    let mut header = [0; 2];
+    stream.read_exact(&mut header).await?;
+    if header[0] != VERSION {
+        return Err(io::ErrorKind::InvalidData.into());
+    }
+    let mut methods = vec![0; header[1].into()];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&NO_AUTH) {
+        return stream.write_all(&[VERSION, NO_ACCEPTABLE_METHODS]).await;
+    }
+    stream.write_all(&[VERSION, NO_AUTH]).await?;
+
+    // Request: the version, command, a reserved byte, then the address and port.
+    let mut request = [0; 4];
+    stream.read_exact(&mut request).await?;
+    let host = match request[3] {
+        IPV4 => {
+            let mut ip = [0; 4];
+            stream.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        }
+        IPV6 => {
+            let mut ip = [0; 16];
+            stream.read_exact(&mut ip).await?;
+            Ipv6Addr::from(ip).to_string()
+        }
+        DOMAIN => {
+            let len = stream.read_u8().await?;
+            let mut domain = vec![0; len.into()];
+            stream.read_exact(&mut domain).await?;
+            String::from_utf8_lossy(&domain).into_owned()
+        }
+        _ => return reply(&mut stream, ADDRESS_TYPE_NOT_SUPPORTED).await,
+    };
+    let port = stream.read_u16().await?;
+
+    if request[1] != CONNECT {
+        return reply(&mut stream, COMMAND_NOT_SUPPORTED).await;
+    }
+
+    let Ok(mut channel) = connection.direct_tcpip(&host, port).await else {
+        return reply(&mut stream, HOST_UNREACHABLE).await;
+    };
+    reply(&mut stream, SUCCEEDED).await?;
+    copy_bidirectional(&mut stream, &mut channel).await?;
+    Ok(())
+}
+
+/// Answer a request, with an unspecified bound address since the channel hides it.
+async fn reply(stream: &mut TcpStream, status: u8) -> io::Result<()> {
+    stream
+        .write_all(&[VERSION, status, 0, IPV4, 0, 0, 0, 0, 0, 0])
+        .await
+}
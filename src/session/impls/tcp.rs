@@ -0,0 +1,187 @@
+use async_trait::async_trait;
+use miette::{bail, miette, IntoDiagnostic, Result};
+use tokio::{
+    io::{self, AsyncRead, AsyncWrite, AsyncWriteExt, WriteHalf},
+    net::{TcpStream, UnixStream},
+    task::JoinHandle,
+};
+use url::Url;
+
+use super::{proxy, tls};
+use crate::session::{
+    output::Output, scheme::Scheme, store::StoredSession, ConnectionInfo, Session,
+};
+
+/// A raw byte stream session, over plain TCP, TLS or a Unix domain socket.
+pub struct Tcp {
+    url: Url,
+    writer: WriteHalf<Box<dyn Stream>>,
+    reader: JoinHandle<()>,
+    output: Output,
+    details: Vec<(&'static str, String)>,
+    is_inbound: bool,
+    has_pty: bool,
+}
+
+pub trait Stream: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Sync + Unpin> Stream for T {}
+
+impl Tcp {
+    /// Wrap a connection accepted by a listener.
+    pub fn accept(stream: TcpStream) -> Result<StoredSession> {
+        let peer = stream.peer_addr().into_diagnostic()?;
+        let url = Url::parse(&format!("tcp://{peer}")).into_diagnostic()?;
+
+        let mut tcp = Self::new(url.clone(), Box::new(stream), Vec::new());
+        tcp.is_inbound = true;
+        let connection_info = ConnectionInfo {
+            url,
+            scheme: Scheme::Tcp,
+            is_inbound: true,
+        };
+
+        Ok(StoredSession {
+            connection_info,
+            session: Box::new(tcp),
+        })
+    }
+
+    fn new(url: Url, stream: Box<dyn Stream>, details: Vec<(&'static str, String)>) -> Self {
+        let output = Output::default();
+        let (reader, writer) = io::split(stream);
+        let reader = output.spawn_reader(reader);
+
+        Self {
+            url,
+            writer,
+            reader,
+            output,
+            details,
+            is_inbound: false,
+            has_pty: false,
+        }
+    }
+}
+
+#[async_trait]
+impl Session for Tcp {
+    async fn connect(url: Url) -> Result<StoredSession> {
+        let scheme = url.scheme().parse()?;
+        let (stream, details) = create_stream(&url).await?;
+
+        let tcp = Self::new(url.clone(), stream, details);
+        let connection_info = ConnectionInfo {
+            url,
+            scheme,
+            is_inbound: false,
+        };
+
+        Ok(StoredSession {
+            connection_info,
+            session: Box::new(tcp),
+        })
+    }
+
+    fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
+
+    async fn is_connected(&mut self) -> bool {
+        !self.output.is_closed()
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        if !self.is_connected().await {
+            if self.is_inbound {
+                bail!("Cannot reconnect to an inbound connection.");
+            }
+
+            let (stream, details) = create_stream(&self.url).await?;
+            let (reader, writer) = io::split(stream);
+            self.output.reopen();
+            self.reader = self.output.spawn_reader(reader);
+            self.writer = writer;
+            self.details = details;
+            self.has_pty = false;
+        }
+
+        Ok(())
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        let res = self.writer.write_all(data).await.into_diagnostic();
+        if res.is_err() {
+            self.output.close();
+        }
+        res
+    }
+
+    fn has_pty(&self) -> bool {
+        self.has_pty
+    }
+
+    fn can_upgrade(&self) -> bool {
+        true
+    }
+
+    fn set_pty(&mut self) {
+        self.has_pty = true;
+    }
+
+    fn details(&self) -> Vec<(&'static str, String)> {
+        self.details.clone()
+    }
+
+    async fn close(&mut self) {
+        self.writer.shutdown().await.ok();
+        self.reader.abort();
+        self.output.close();
+    }
+}
+
+/// Connect to `url`, returning the stream and a description of any TLS layer or socket peer.
+async fn create_stream(url: &Url) -> Result<(Box<dyn Stream>, Vec<(&'static str, String)>)> {
+    let scheme = url.scheme().parse()?;
+    if let Scheme::Unix = scheme {
+        let path = url
+            .to_file_path()
+            .map_err(|()| miette!("Invalid socket path `{}`.", url.path()))?;
+        let stream = UnixStream::connect(path).await.into_diagnostic()?;
+        let details = unix_details(&stream);
+        return Ok((Box::new(stream), details));
+    }
+
+    let host = url.host_str().ok_or_else(|| miette!("No host provided."))?;
+    let port = url.port().ok_or_else(|| miette!("No port provided."))?;
+
+    let stream = proxy::connect(url, host, port).await?;
+    match scheme {
+        Scheme::Tls => {
+            let stream = tls::connect(url, stream).await?;
+            let details = tls::details(&stream);
+            Ok((Box::new(stream), details))
+        }
+        _ => Ok((Box::new(stream), Vec::new())),
+    }
+}
+
+/// Describe the process listening on the other end of `stream`.
+fn unix_details(stream: &UnixStream) -> Vec<(&'static str, String)> {
+    let Ok(cred) = stream.peer_cred() else {
+        return Vec::new();
+    };
+
+    let mut details = vec![
+        ("Peer UID", cred.uid().to_string()),
+        ("Peer GID", cred.gid().to_string()),
+    ];
+    if let Some(pid) = cred.pid() {
+        details.push(("Peer PID", pid.to_string()));
+    }
+    details
+}
@@ -0,0 +1,437 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use miette::{miette, IntoDiagnostic, Result};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+    sync::Mutex as AsyncMutex,
+    task::JoinHandle,
+};
+use url::Url;
+
+use super::proxy;
+use crate::session::{
+    output::Output, scheme::Scheme, store::StoredSession, ConnectionInfo, Session,
+};
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const ECHO: u8 = 1;
+const SGA: u8 = 3;
+const TTYPE: u8 = 24;
+const NAWS: u8 = 31;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+type Writer = Arc<AsyncMutex<OwnedWriteHalf>>;
+
+/// A telnet connection, with option negotiation handled in the background.
+pub struct Telnet {
+    url: Url,
+    writer: Writer,
+    reader: JoinHandle<()>,
+    output: Output,
+    options: Arc<Mutex<Options>>,
+    /// The remote shell was given a pty by `#upgrade`, so echoes whatever was negotiated.
+    is_upgraded: bool,
+}
+
+/// Options currently in effect.
+#[derive(Default, Clone, Copy)]
+struct Options {
+    /// The server echoes input, so the local terminal should not.
+    remote_echo: bool,
+    remote_sga: bool,
+    local_sga: bool,
+    naws: bool,
+    ttype: bool,
+}
+
+impl Telnet {
+    async fn open(url: Url, output: Output) -> Result<Self> {
+        let host = url.host_str().ok_or_else(|| miette!("No host provided."))?;
+        let port = url.port().unwrap_or(23);
+        let stream = proxy::connect(&url, host, port).await?;
+
+        let (reader, writer) = stream.into_split();
+        let writer = Arc::new(AsyncMutex::new(writer));
+        let options = Arc::default();
+        let reader = spawn_reader(reader, writer.clone(), output.clone(), Arc::clone(&options));
+
+        Ok(Self {
+            url,
+            writer,
+            reader,
+            output,
+            options,
+            is_upgraded: false,
+        })
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        let res = self.writer.lock().await.write_all(data).await;
+        if res.is_err() {
+            self.output.close();
+        }
+        res.into_diagnostic()
+    }
+}
+
+#[async_trait]
+impl Session for Telnet {
+    async fn connect(url: Url) -> Result<StoredSession> {
+        let telnet = Self::open(url.clone(), Output::default()).await?;
+        let connection_info = ConnectionInfo {
+            url,
+            scheme: Scheme::Telnet,
+            is_inbound: false,
+        };
+
+        Ok(StoredSession {
+            connection_info,
+            session: Box::new(telnet),
+        })
+    }
+
+    fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
+
+    async fn is_connected(&mut self) -> bool {
+        !self.output.is_closed()
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        if !self.is_connected().await {
+            self.reader.abort();
+            // A failed attempt must leave the session closed, or it would still list as
+            // connected.
+            let output = self.output.clone();
+            output.reopen();
+            *self = Self::open(self.url.clone(), output.clone())
+                .await
+                .inspect_err(|_| output.close())?;
+        }
+
+        Ok(())
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        // Lines end in CR LF and a bare CR is followed by NUL, as the NVT expects.
+        let mut encoded = Vec::with_capacity(data.len());
+        for &b in data {
+            match b {
+                IAC => encoded.extend([IAC, IAC]),
+                b'\n' => encoded.extend(b"\r\n"),
+                b'\r' => encoded.extend(b"\r\0"),
+                _ => encoded.push(b),
+            }
+        }
+        self.write(&encoded).await
+    }
+
+    fn has_pty(&self) -> bool {
+        self.is_upgraded || self.options.lock().unwrap().remote_echo
+    }
+
+    fn can_upgrade(&self) -> bool {
+        true
+    }
+
+    fn set_pty(&mut self) {
+        self.is_upgraded = true;
+    }
+
+    async fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        if self.options.lock().unwrap().naws {
+            self.write(&naws(cols, rows)).await?;
+        }
+        Ok(())
+    }
+
+    fn details(&self) -> Vec<(&'static str, String)> {
+        let options = *self.options.lock().unwrap();
+        let names: Vec<_> = [
+            (options.remote_echo, "ECHO"),
+            (options.remote_sga || options.local_sga, "SGA"),
+            (options.naws, "NAWS"),
+            (options.ttype, "TTYPE"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect();
+
+        let names = if names.is_empty() {
+            "None".to_string()
+        } else {
+            names.join(", ")
+        };
+        vec![("Options", names)]
+    }
+
+    async fn close(&mut self) {
+        self.writer.lock().await.shutdown().await.ok();
+        self.reader.abort();
+        self.output.close();
+    }
+}
+
+/// Read from `reader`, pushing data to `output` and answering negotiation through `writer`.
+fn spawn_reader(
+    mut reader: OwnedReadHalf,
+    writer: Writer,
+    output: Output,
+    options: Arc<Mutex<Options>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut parser = Parser::default();
+        let mut buf = vec![0; 4096];
+        while let Ok(n @ 1..) = reader.read(&mut buf).await {
+            let (data, reply) = parser.feed(&buf[..n], &options);
+            if !data.is_empty() {
+                output.push(&data);
+            }
+            if !reply.is_empty() && writer.lock().await.write_all(&reply).await.is_err() {
+                break;
+            }
+        }
+        output.close();
+    })
+}
+
+#[derive(Default)]
+enum State {
+    #[default]
+    Data,
+    Iac,
+    Option(u8),
+    Sub,
+    SubIac,
+}
+
+#[derive(Default)]
+struct Parser {
+    state: State,
+    sub: Vec<u8>,
+    /// The last data byte was a CR, which may have ended the previous read.
+    last_was_cr: bool,
+}
+
+impl Parser {
+    /// Split `input` into data and the replies it calls for.
+    fn feed(&mut self, input: &[u8], options: &Mutex<Options>) -> (Vec<u8>, Vec<u8>) {
+        let mut data = Vec::with_capacity(input.len());
+        let mut reply = Vec::new();
+        let mut options = options.lock().unwrap();
+
+        for &b in input {
+            self.state = match self.state {
+                State::Data if b == IAC => State::Iac,
+                State::Data => {
+                    // NUL only pads a bare CR.
+                    if !(b == 0 && self.last_was_cr) {
+                        data.push(b);
+                    }
+                    self.last_was_cr = b == b'\r';
+                    State::Data
+                }
+                State::Iac => match b {
+                    IAC => {
+                        data.push(IAC);
+                        self.last_was_cr = false;
+                        State::Data
+                    }
+                    WILL | WONT | DO | DONT => State::Option(b),
+                    SB => {
+                        self.sub.clear();
+                        State::Sub
+                    }
+                    _ => State::Data,
+                },
+                State::Option(command) => {
+                    reply.extend(negotiate(&mut options, command, b));
+                    State::Data
+                }
+                State::Sub if b == IAC => State::SubIac,
+                State::Sub => {
+                    self.sub.push(b);
+                    State::Sub
+                }
+                State::SubIac => match b {
+                    SE => {
+                        if self.sub[..] == [TTYPE, TTYPE_SEND] {
+                            reply.extend(terminal_type());
+                        }
+                        State::Data
+                    }
+                    _ => {
+                        self.sub.push(b);
+                        State::Sub
+                    }
+                },
+            };
+        }
+
+        (data, reply)
+    }
+}
+
+/// Respond to `command` for `option`, only acknowledging changes so negotiation can't loop.
+fn negotiate(options: &mut Options, command: u8, option: u8) -> Vec<u8> {
+    match (command, option) {
+        (WILL, ECHO) => enable(&mut options.remote_echo, DO, option),
+        (WILL, SGA) => enable(&mut options.remote_sga, DO, option),
+        (WILL, _) => vec![IAC, DONT, option],
+        (WONT, ECHO) => disable(&mut options.remote_echo, DONT, option),
+        (WONT, SGA) => disable(&mut options.remote_sga, DONT, option),
+        (DO, SGA) => enable(&mut options.local_sga, WILL, option),
+        (DO, TTYPE) => enable(&mut options.ttype, WILL, option),
+        (DO, NAWS) => {
+            let mut reply = enable(&mut options.naws, WILL, option);
+            let (cols, rows) = crossterm::terminal::size()
+                .ok()
+                .filter(|&(w, h)| w > 0 && h > 0)
+                .unwrap_or((80, 24));
+            reply.extend(naws(cols, rows));
+            reply
+        }
+        (DO, _) => vec![IAC, WONT, option],
+        (DONT, SGA) => disable(&mut options.local_sga, WONT, option),
+        (DONT, TTYPE) => disable(&mut options.ttype, WONT, option),
+        (DONT, NAWS) => disable(&mut options.naws, WONT, option),
+        _ => Vec::new(),
+    }
+}
+
+fn enable(enabled: &mut bool, reply: u8, option: u8) -> Vec<u8> {
+    if *enabled {
+        return Vec::new();
+    }
+    *enabled = true;
+    vec![IAC, reply, option]
+}
+
+fn disable(enabled: &mut bool, reply: u8, option: u8) -> Vec<u8> {
+    if !*enabled {
+        return Vec::new();
+    }
+    *enabled = false;
+    vec![IAC, reply, option]
+}
+
+fn naws(cols: u16, rows: u16) -> Vec<u8> {
+    let mut sub = vec![IAC, SB, NAWS];
+    for b in cols.to_be_bytes().into_iter().chain(rows.to_be_bytes()) {
+        sub.push(b);
+        if b == IAC {
+            sub.push(IAC);
+        }
+    }
+    sub.extend([IAC, SE]);
+    sub
+}
+
+fn terminal_type() -> Vec<u8> {
+    let term = std::env::var("TERM").unwrap_or_else(|_| "xterm".into());
+    let mut sub = vec![IAC, SB, TTYPE, TTYPE_IS];
+    sub.extend(term.to_uppercase().bytes());
+    sub.extend([IAC, SE]);
+    sub
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(input: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        Parser::default().feed(input, &Mutex::default())
+    }
+
+    #[test]
+    fn unescapes_iac() {
+        assert_eq!(
+            feed(&[b'a', IAC, IAC, b'b']),
+            (vec![b'a', IAC, b'b'], vec![])
+        );
+    }
+
+    #[test]
+    fn drops_nul_after_cr() {
+        assert_eq!(feed(b"a\r\0b\0"), (b"a\rb\0".to_vec(), vec![]));
+    }
+
+    #[test]
+    fn drops_nul_after_cr_ending_a_read() {
+        let mut parser = Parser::default();
+        let options = Mutex::default();
+        assert_eq!(parser.feed(b"a\r", &options), (b"a\r".to_vec(), vec![]));
+        assert_eq!(parser.feed(b"\0b", &options), (b"b".to_vec(), vec![]));
+    }
+
+    #[test]
+    fn keeps_state_across_reads() {
+        let mut parser = Parser::default();
+        let options = Mutex::default();
+        assert_eq!(parser.feed(&[b'a', IAC], &options), (vec![b'a'], vec![]));
+        assert_eq!(
+            parser.feed(&[WILL, ECHO], &options),
+            (vec![], vec![IAC, DO, ECHO])
+        );
+        assert!(options.lock().unwrap().remote_echo);
+    }
+
+    #[test]
+    fn unescapes_iac_in_subnegotiation() {
+        let mut parser = Parser::default();
+        let input = [IAC, SB, TTYPE, IAC, IAC, IAC, SE, b'a'];
+        assert_eq!(parser.feed(&input, &Mutex::default()), (vec![b'a'], vec![]));
+        assert_eq!(parser.sub, [TTYPE, IAC]);
+    }
+
+    #[test]
+    fn answers_terminal_type_request() {
+        let (data, reply) = feed(&[IAC, SB, TTYPE, TTYPE_SEND, IAC, SE]);
+        assert!(data.is_empty());
+        assert!(reply.starts_with(&[IAC, SB, TTYPE, TTYPE_IS]));
+        assert!(reply.ends_with(&[IAC, SE]));
+    }
+
+    #[test]
+    fn acknowledges_changes_only() {
+        let mut options = Options::default();
+        assert_eq!(negotiate(&mut options, WILL, ECHO), [IAC, DO, ECHO]);
+        assert!(negotiate(&mut options, WILL, ECHO).is_empty());
+        assert_eq!(negotiate(&mut options, DO, SGA), [IAC, WILL, SGA]);
+        assert!(negotiate(&mut options, DO, SGA).is_empty());
+        assert_eq!(negotiate(&mut options, WONT, ECHO), [IAC, DONT, ECHO]);
+        assert!(negotiate(&mut options, WONT, ECHO).is_empty());
+        assert_eq!(negotiate(&mut options, DONT, SGA), [IAC, WONT, SGA]);
+        assert!(negotiate(&mut options, DONT, SGA).is_empty());
+    }
+
+    #[test]
+    fn refuses_unknown_options() {
+        let mut options = Options::default();
+        assert_eq!(negotiate(&mut options, WILL, 42), [IAC, DONT, 42]);
+        assert_eq!(negotiate(&mut options, DO, 42), [IAC, WONT, 42]);
+        assert!(negotiate(&mut options, WONT, 42).is_empty());
+        assert!(negotiate(&mut options, DONT, 42).is_empty());
+    }
+
+    #[test]
+    fn escapes_iac_in_window_size() {
+        assert_eq!(naws(255, 24), [IAC, SB, NAWS, 0, IAC, IAC, 0, 24, IAC, SE]);
+    }
+}
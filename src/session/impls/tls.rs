@@ -0,0 +1,210 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use miette::{miette, Context, IntoDiagnostic, Result};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{self, ring, CryptoProvider},
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+        ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    },
+    TlsConnector,
+};
+use url::Url;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// Query parameters read by [`Options::from_url`].
+pub const PARAMS: [&str; 5] = ["sni", "insecure", "ca", "cert", "key"];
+
+/// TLS settings taken from the query of a `tls://` URL.
+///
+/// `sni` overrides the server name, `insecure` skips certificate verification, `ca` trusts
+/// only the given PEM bundle, and `cert` and `key` present a client certificate.
+struct Options {
+    sni: Option<String>,
+    insecure: bool,
+    ca: Option<PathBuf>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+}
+
+impl Options {
+    fn from_url(url: &Url) -> Self {
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.into_owned())
+        };
+
+        Self {
+            sni: param("sni"),
+            insecure: param("insecure").is_some_and(|v| v != "false" && v != "0"),
+            ca: param("ca").map(PathBuf::from),
+            cert: param("cert").map(PathBuf::from),
+            key: param("key").map(PathBuf::from),
+        }
+    }
+}
+
+/// Wrap `stream` in TLS according to the options in `url`.
+pub async fn connect(url: &Url, stream: TcpStream) -> Result<TlsStream<TcpStream>> {
+    let sni = Options::from_url(url).sni;
+    let name = match sni.as_deref() {
+        Some(sni) => sni,
+        None => url.host_str().ok_or_else(|| miette!("No host provided."))?,
+    };
+    let name = ServerName::try_from(name.to_string())
+        .map_err(|_| miette!("Invalid server name `{}`.", name))?;
+
+    let config = client_config(url)?;
+    TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+        .into_diagnostic()
+        .wrap_err("TLS handshake failed")
+}
+
+/// Build a client configuration from the TLS options in `url`.
+pub fn client_config(url: &Url) -> Result<ClientConfig> {
+    let options = Options::from_url(url);
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .into_diagnostic()?;
+
+    let builder = if options.insecure {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerifier(provider)))
+    } else {
+        let mut roots = RootCertStore::empty();
+        match &options.ca {
+            Some(ca) => {
+                for cert in read_certs(ca)? {
+                    roots.add(cert).into_diagnostic()?;
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+        builder.with_root_certificates(roots)
+    };
+
+    match (&options.cert, &options.key) {
+        (Some(cert), key) => {
+            let certs = read_certs(cert)?;
+            // The key may be bundled with the certificate.
+            let key = read_key(key.as_deref().unwrap_or(cert))?;
+            builder.with_client_auth_cert(certs, key).into_diagnostic()
+        }
+        (None, Some(_)) => Err(miette!("A client key needs a client certificate.")),
+        (None, None) => Ok(builder.with_no_client_auth()),
+    }
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to open `{}`", path.display()))?;
+    rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<_, _>>()
+        .into_diagnostic()
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to open `{}`", path.display()))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .into_diagnostic()?
+        .ok_or_else(|| miette!("No private key found in `{}`.", path.display()))
+}
+
+/// Describe the negotiated parameters and peer certificate of `stream`.
+pub fn details(stream: &TlsStream<TcpStream>) -> Vec<(&'static str, String)> {
+    let (_, conn) = stream.get_ref();
+    let mut details = Vec::new();
+
+    if let Some(version) = conn.protocol_version() {
+        details.push(("Protocol", format!("{version:?}")));
+    }
+    if let Some(suite) = conn.negotiated_cipher_suite() {
+        details.push(("Cipher", format!("{:?}", suite.suite())));
+    }
+
+    let Some(der) = conn.peer_certificates().and_then(|certs| certs.first()) else {
+        return details;
+    };
+    if let Ok((_, cert)) = X509Certificate::from_der(der) {
+        details.push(("Subject", cert.subject().to_string()));
+        details.push(("Issuer", cert.issuer().to_string()));
+        let validity = cert.validity();
+        details.push(("Not before", validity.not_before.to_string()));
+        details.push(("Not after", validity.not_after.to_string()));
+        details.push(("Serial", cert.raw_serial_as_string()));
+    }
+    let fingerprint = Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":");
+    details.push(("SHA-256", fingerprint));
+
+    details
+}
+
+/// Accepts any certificate, still checking that the handshake is signed by it.
+#[derive(Debug)]
+struct NoVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use async_trait::async_trait;
+use miette::{miette, IntoDiagnostic, Result};
+use tokio::{
+    io::Interest,
+    net::{lookup_host, UdpSocket},
+    task::JoinHandle,
+};
+use url::Url;
+
+use crate::session::{
+    output::Output, scheme::Scheme, store::StoredSession, ConnectionInfo, Session,
+};
+
+/// Largest possible UDP payload.
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// A UDP "connection", where every send is one datagram.
+pub struct Udp {
+    url: Url,
+    socket: Arc<UdpSocket>,
+    reader: JoinHandle<()>,
+    output: Output,
+    /// Show received datagrams as a hexdump rather than raw bytes.
+    hexdump: Arc<AtomicBool>,
+}
+
+impl Udp {
+    async fn open(url: Url, output: Output, hexdump: Arc<AtomicBool>) -> Result<Self> {
+        let host = url.host_str().ok_or_else(|| miette!("No host provided."))?;
+        let port = url.port().ok_or_else(|| miette!("No port provided."))?;
+        let peer = lookup_host((host, port))
+            .await
+            .into_diagnostic()?
+            .next()
+            .ok_or_else(|| miette!("Failed to resolve `{}`.", host))?;
+
+        let local: SocketAddr = match peer {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await.into_diagnostic()?;
+        socket.connect(peer).await.into_diagnostic()?;
+        let socket = Arc::new(socket);
+
+        let reader = spawn_reader(Arc::clone(&socket), output.clone(), Arc::clone(&hexdump));
+
+        Ok(Self {
+            url,
+            socket,
+            reader,
+            output,
+            hexdump,
+        })
+    }
+}
+
+#[async_trait]
+impl Session for Udp {
+    async fn connect(url: Url) -> Result<StoredSession> {
+        let hexdump = url
+            .query_pairs()
+            .find(|(k, _)| k == "hexdump")
+            .is_some_and(|(_, v)| v != "false" && v != "0");
+        let udp = Self::open(
+            url.clone(),
+            Output::default(),
+            Arc::new(AtomicBool::new(hexdump)),
+        )
+        .await?;
+        let connection_info = ConnectionInfo {
+            url,
+            scheme: Scheme::Udp,
+            is_inbound: false,
+        };
+
+        Ok(StoredSession {
+            connection_info,
+            session: Box::new(udp),
+        })
+    }
+
+    fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
+
+    async fn is_connected(&mut self) -> bool {
+        !self.output.is_closed()
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        if !self.is_connected().await {
+            self.reader.abort();
+            let output = self.output.clone();
+            output.reopen();
+            *self = Self::open(self.url.clone(), output.clone(), Arc::clone(&self.hexdump))
+                .await
+                .inspect_err(|_| output.close())?;
+        }
+
+        Ok(())
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        match self.socket.send(data).await {
+            // A previous datagram was refused; this one may still get through.
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(()),
+            res => res.map(|_| ()).into_diagnostic(),
+        }
+    }
+
+    fn set_hexdump(&mut self, enabled: bool) -> Result<()> {
+        self.hexdump.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn details(&self) -> Vec<(&'static str, String)> {
+        let mut details = Vec::new();
+        if let Ok(addr) = self.socket.local_addr() {
+            details.push(("Local address", addr.to_string()));
+        }
+        if let Ok(addr) = self.socket.peer_addr() {
+            details.push(("Peer address", addr.to_string()));
+        }
+        let display = if self.hexdump.load(Ordering::Relaxed) {
+            "Hexdump"
+        } else {
+            "Raw"
+        };
+        details.push(("Display", display.to_string()));
+        details
+    }
+
+    async fn close(&mut self) {
+        self.reader.abort();
+        self.output.close();
+    }
+}
+
+fn spawn_reader(
+    socket: Arc<UdpSocket>,
+    output: Output,
+    hexdump: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            tokio::select! {
+                res = socket.recv(&mut buf) => match res {
+                    Ok(n) if hexdump.load(Ordering::Relaxed) => {
+                        output.push(format_hexdump(&buf[..n]).as_bytes());
+                    }
+                    Ok(n) => output.push(&buf[..n]),
+                    Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                        output.push(b"[!] Port unreachable.\n");
+                    }
+                    Err(_) => break,
+                },
+                // `recv` isn't woken by the error an ICMP port unreachable leaves behind.
+                res = socket.async_io(Interest::ERROR, || pending_error(&socket)) => match res {
+                    Ok(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                        output.push(b"[!] Port unreachable.\n");
+                    }
+                    _ => break,
+                },
+            }
+        }
+        output.close();
+    })
+}
+
+/// Take the socket's pending error, if there is one.
+fn pending_error(socket: &UdpSocket) -> io::Result<io::Error> {
+    socket
+        .take_error()?
+        .ok_or_else(|| ErrorKind::WouldBlock.into())
+}
+
+/// Format `data` like `hexdump -C`.
+fn format_hexdump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        out.push_str(&format!("{:08x} ", i * 16));
+        for j in 0..16 {
+            if j % 8 == 0 {
+                out.push(' ');
+            }
+            match line.get(j) {
+                Some(b) => out.push_str(&format!("{b:02x} ")),
+                None => out.push_str("   "),
+            }
+        }
+
+        let ascii: String = line
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(" |{ascii}|\n"));
+    }
+    out.push_str(&format!("{:08x}\n", data.len()));
+    out
+}
@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures_util::{stream::SplitSink, SinkExt, StreamExt};
+use miette::{bail, miette, IntoDiagnostic, Result};
+use tokio::{net::TcpStream, task::JoinHandle};
+use tokio_tungstenite::{
+    client_async_tls_with_config,
+    tungstenite::{
+        client::IntoClientRequest,
+        http::{header::COOKIE, HeaderName, HeaderValue},
+        Message,
+    },
+    Connector, MaybeTlsStream, WebSocketStream,
+};
+use url::Url;
+
+use super::{proxy, strip_query, tls};
+use crate::{
+    config::Http,
+    session::{output::Output, store::StoredSession, ConnectionInfo, Session},
+};
+
+type Sink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+
+/// Query parameters consumed by rally rather than passed on to the server.
+const PARAMS: [&str; 3] = ["header", "cookie", "frame"];
+
+/// A WebSocket, with text and binary messages mapped onto the session's byte stream.
+pub struct Ws {
+    url: Url,
+    sink: Sink,
+    reader: JoinHandle<()>,
+    output: Output,
+    frame: Frame,
+    details: Vec<(&'static str, String)>,
+    /// Whether `#upgrade` spawned a pty for the shell behind the socket.
+    has_pty: bool,
+}
+
+/// Kind of message used for input.
+#[derive(Clone, Copy)]
+enum Frame {
+    Text,
+    Binary,
+}
+
+impl Frame {
+    fn from_url(url: &Url) -> Result<Self> {
+        match url.query_pairs().find(|(k, _)| k == "frame") {
+            None => Ok(Self::Text),
+            Some((_, frame)) => match &*frame {
+                "text" => Ok(Self::Text),
+                "binary" => Ok(Self::Binary),
+                _ => bail!("Unknown frame type `{}`.", frame),
+            },
+        }
+    }
+}
+
+impl Ws {
+    async fn open(url: Url, output: Output) -> Result<Self> {
+        let frame = Frame::from_url(&url)?;
+        let (stream, details) = create_stream(&url).await?;
+        let (sink, mut stream) = stream.split();
+
+        let reader = {
+            let output = output.clone();
+            tokio::spawn(async move {
+                while let Some(Ok(msg)) = stream.next().await {
+                    match msg {
+                        Message::Text(text) => output.push(text.as_bytes()),
+                        Message::Binary(data) => output.push(&data),
+                        Message::Close(_) => break,
+                        _ => {}
+                    }
+                }
+                output.close();
+            })
+        };
+
+        Ok(Self {
+            url,
+            sink,
+            reader,
+            output,
+            frame,
+            details,
+            has_pty: false,
+        })
+    }
+}
+
+#[async_trait]
+impl Session for Ws {
+    async fn connect(url: Url) -> Result<StoredSession> {
+        let scheme = url.scheme().parse()?;
+        let ws = Self::open(url.clone(), Output::default()).await?;
+        let connection_info = ConnectionInfo {
+            url,
+            scheme,
+            is_inbound: false,
+        };
+
+        Ok(StoredSession {
+            connection_info,
+            session: Box::new(ws),
+        })
+    }
+
+    fn url(&self) -> &Url {
+        &self.url
+    }
+
+    fn output(&self) -> &Output {
+        &self.output
+    }
+
+    async fn is_connected(&mut self) -> bool {
+        !self.output.is_closed()
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        if !self.is_connected().await {
+            self.reader.abort();
+            // Reopened first since the new reader may close it straight away, and closed
+            // again if connecting fails so the session isn't reported as connected.
+            let output = self.output.clone();
+            output.reopen();
+            *self = Self::open(self.url.clone(), output.clone())
+                .await
+                .inspect_err(|_| output.close())?;
+        }
+
+        Ok(())
+    }
+
+    async fn send(&mut self, data: &[u8]) -> Result<()> {
+        let msg = match self.frame {
+            Frame::Text => Message::Text(String::from_utf8_lossy(data).into_owned()),
+            Frame::Binary => Message::Binary(data.to_vec()),
+        };
+
+        let res = self.sink.send(msg).await.into_diagnostic();
+        if res.is_err() {
+            self.output.close();
+        }
+        res
+    }
+
+    fn has_pty(&self) -> bool {
+        self.has_pty
+    }
+
+    fn can_upgrade(&self) -> bool {
+        true
+    }
+
+    fn set_pty(&mut self) {
+        self.has_pty = true;
+    }
+
+    fn details(&self) -> Vec<(&'static str, String)> {
+        self.details.clone()
+    }
+
+    async fn close(&mut self) {
+        self.sink.close().await.ok();
+        self.reader.abort();
+        self.output.close();
+    }
+}
+
+async fn create_stream(
+    url: &Url,
+) -> Result<(
+    WebSocketStream<MaybeTlsStream<TcpStream>>,
+    Vec<(&'static str, String)>,
+)> {
+    let mut request = strip_query(url, &PARAMS)
+        .as_str()
+        .into_client_request()
+        .into_diagnostic()?;
+
+    let http = Http::from_url(url);
+    let headers = request.headers_mut();
+    for (name, value) in &http.headers {
+        let name = HeaderName::try_from(name).into_diagnostic()?;
+        let value = HeaderValue::try_from(value).into_diagnostic()?;
+        headers.insert(name, value);
+    }
+    if let Some(cookies) = http.cookie_header() {
+        headers.insert(COOKIE, HeaderValue::try_from(cookies).into_diagnostic()?);
+    }
+
+    let connector = match url.scheme() {
+        "wss" => Connector::Rustls(Arc::new(tls::client_config(url)?)),
+        _ => Connector::Plain,
+    };
+    let host = url.host_str().ok_or_else(|| miette!("No host provided."))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| miette!("No port provided."))?;
+    let stream = proxy::connect(url, host, port).await?;
+    let (stream, response) = client_async_tls_with_config(request, stream, None, Some(connector))
+        .await
+        .map_err(|e| miette!("WebSocket handshake failed: {e}"))?;
+
+    let mut details = vec![("Response", response.status().to_string())];
+    if let Some(protocol) = response.headers().get("sec-websocket-protocol") {
+        details.push((
+            "Subprotocol",
+            String::from_utf8_lossy(protocol.as_bytes()).into(),
+        ));
+    }
+    if let MaybeTlsStream::Rustls(stream) = stream.get_ref() {
+        details.extend(tls::details(stream));
+    }
+
+    Ok((stream, details))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{net::TcpListener, time::timeout};
+    use tokio_tungstenite::accept_async;
+
+    use super::*;
+
+    /// Accept one WebSocket on a free port and echo its messages back.
+    async fn echo_server() -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            while let Some(Ok(msg)) = ws.next().await {
+                if msg.is_text() || msg.is_binary() {
+                    ws.send(msg).await.unwrap();
+                }
+            }
+        });
+        format!("ws://{addr}/").parse().unwrap()
+    }
+
+    async fn read_until(output: &Output, expected: &[u8]) -> Vec<u8> {
+        let mut data = output.take_unread();
+        timeout(Duration::from_secs(5), async {
+            while data.len() < expected.len() {
+                output.changed().await;
+                data.extend(output.take_unread());
+            }
+        })
+        .await
+        .ok();
+        data
+    }
+
+    #[tokio::test]
+    async fn echoes_text() {
+        let mut ws = Ws::open(echo_server().await, Output::default())
+            .await
+            .unwrap();
+        ws.send(b"hello").await.unwrap();
+        assert_eq!(read_until(ws.output(), b"hello").await, b"hello");
+    }
+
+    #[tokio::test]
+    async fn echoes_binary() {
+        let mut url = echo_server().await;
+        url.set_query(Some("frame=binary"));
+        let mut ws = Ws::open(url, Output::default()).await.unwrap();
+        ws.send(&[0, 1, 0xff]).await.unwrap();
+        assert_eq!(read_until(ws.output(), &[0, 1, 0xff]).await, [0, 1, 0xff]);
+    }
+}
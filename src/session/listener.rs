@@ -0,0 +1,133 @@
+use std::net::SocketAddr;
+
+use indexmap::IndexMap;
+use miette::{miette, IntoDiagnostic, Result};
+use owo_colors::OwoColorize;
+use tabled::{builder::Builder, Table};
+use tokio::{
+    net::TcpListener,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+use super::{impls::tcp::Tcp, store::StoredSession};
+
+pub struct Listener {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+/// A connection taken by a listener, announced once it's registered as a session.
+pub struct Accepted {
+    pub session: StoredSession,
+    pub peer: SocketAddr,
+    pub listener: String,
+}
+
+impl Accepted {
+    /// Announce the connection, registered as session `id`.
+    pub fn notice(&self, id: &str) -> String {
+        format!(
+            "{} Connection from {} on listener `{}` (session `{}`).",
+            "[+]".green(),
+            self.peer,
+            self.listener,
+            id
+        )
+    }
+}
+
+pub struct Listeners {
+    listeners: IndexMap<String, Listener>,
+    next_id: usize,
+    tx: UnboundedSender<Accepted>,
+    rx: UnboundedReceiver<Accepted>,
+}
+
+impl Default for Listeners {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            listeners: IndexMap::new(),
+            next_id: 0,
+            tx,
+            rx,
+        }
+    }
+}
+
+impl Listeners {
+    /// Bind `addr` and accept connections in the background.
+    pub async fn add(&mut self, addr: SocketAddr) -> Result<(String, SocketAddr)> {
+        let listener = TcpListener::bind(addr).await.into_diagnostic()?;
+        let addr = listener.local_addr().into_diagnostic()?;
+
+        let id = self.next_id.to_string();
+        self.next_id += 1;
+
+        let tx = self.tx.clone();
+        let listener_id = id.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                let Ok(session) = Tcp::accept(stream) else {
+                    continue;
+                };
+
+                let accepted = Accepted {
+                    session,
+                    peer,
+                    listener: listener_id.clone(),
+                };
+                if tx.send(accepted).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.listeners.insert(id.clone(), Listener { addr, task });
+        Ok((id, addr))
+    }
+
+    pub fn stop<K>(&mut self, id: &K) -> Result<()>
+    where
+        K: AsRef<str>,
+    {
+        let id = id.as_ref();
+        let listener = self
+            .listeners
+            .shift_remove(id)
+            .ok_or_else(|| miette!("No listener found with ID `{}`.", id))?;
+        listener.task.abort();
+        Ok(())
+    }
+
+    /// Take every connection accepted since the last call.
+    pub fn accepted(&mut self) -> Vec<Accepted> {
+        let mut accepted = Vec::new();
+        while let Ok(connection) = self.rx.try_recv() {
+            accepted.push(connection);
+        }
+        accepted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    pub fn table(&self) -> Table {
+        let mut builder = Builder::default();
+        builder.push_record(["ID", "Address"]);
+        for (id, listener) in &self.listeners {
+            builder.push_record([id.to_owned(), listener.addr.to_string()]);
+        }
+        builder.build()
+    }
+}
+
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        for listener in self.listeners.values() {
+            listener.task.abort();
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::VecDeque,
+    io,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::Notify,
+    task::JoinHandle,
+};
+
+use super::{
+    recording::Recording,
+    transcript::{Direction, Transcript},
+};
+
+/// Maximum number of bytes of scrollback kept per session.
+const SCROLLBACK_SIZE: usize = 64 * 1024;
+
+/// Output received from a session, buffered while nobody is attached.
+#[derive(Clone, Default)]
+pub struct Output {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct State {
+    scrollback: VecDeque<u8>,
+    unread: usize,
+    /// Total number of bytes ever pushed.
+    pushed: u64,
+    is_closed: bool,
+    transcript: Option<Writer>,
+    recording: Option<Writer>,
+}
+
+impl State {
+    fn log(&self, direction: Direction, data: &[u8]) {
+        if let Some(transcript) = &self.transcript {
+            transcript.send(direction, data);
+        }
+    }
+
+    fn capture(&self, data: &[u8]) {
+        if let Some(recording) = &self.recording {
+            recording.send(Direction::Output, data);
+        }
+    }
+}
+
+/// Traffic for a [`Writer`], timestamped as it happened rather than when written.
+type Event = (Direction, Instant, Vec<u8>);
+
+/// A transcript or recording written on its own thread, so a slow disk doesn't hold up
+/// the session readers. Dropping it waits for what was sent to be written.
+struct Writer {
+    path: PathBuf,
+    tx: Option<mpsc::Sender<Event>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Writer {
+    fn spawn<F>(path: &Path, name: &'static str, mut record: F) -> Self
+    where
+        F: FnMut(Direction, Instant, &[u8]) -> io::Result<()> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel::<Event>();
+        let thread = thread::spawn(move || {
+            for (direction, time, data) in rx {
+                // A failing file shouldn't take the session down with it.
+                if let Err(e) = record(direction, time, &data) {
+                    eprintln!("Failed to write {name}: {e}");
+                    break;
+                }
+            }
+        });
+
+        Self {
+            path: path.to_path_buf(),
+            tx: Some(tx),
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, direction: Direction, data: &[u8]) {
+        if let Some(tx) = &self.tx {
+            tx.send((direction, Instant::now(), data.to_vec())).ok();
+        }
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Output {
+    pub fn push(&self, data: &[u8]) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.scrollback.extend(data);
+        let overflow = state.scrollback.len().saturating_sub(SCROLLBACK_SIZE);
+        state.scrollback.drain(..overflow);
+        state.unread = (state.unread + data.len()).min(SCROLLBACK_SIZE);
+        state.pushed += data.len() as u64;
+        state.log(Direction::Output, data);
+        state.capture(data);
+        drop(state);
+
+        self.inner.notify.notify_one();
+    }
+
+    /// Record input typed by the user in the transcript, if one is open.
+    pub fn record_input(&self, data: &[u8]) {
+        self.inner.state.lock().unwrap().log(Direction::Input, data);
+    }
+
+    /// Start logging traffic to the transcript at `path`, appending if it exists.
+    pub fn start_transcript(&self, path: &Path) -> io::Result<()> {
+        let mut transcript = Transcript::open(path)?;
+        let writer = Writer::spawn(path, "transcript", move |direction, _, data| {
+            transcript.record(direction, data)
+        });
+        self.inner.state.lock().unwrap().transcript = Some(writer);
+        Ok(())
+    }
+
+    pub fn stop_transcript(&self) {
+        self.inner.state.lock().unwrap().transcript = None;
+    }
+
+    pub fn transcript_path(&self) -> Option<PathBuf> {
+        let state = self.inner.state.lock().unwrap();
+        state.transcript.as_ref().map(|t| t.path.clone())
+    }
+
+    /// Start capturing output to an asciicast recording at `path`.
+    pub fn start_recording(&self, path: &Path, width: u16, height: u16) -> io::Result<()> {
+        let mut recording = Recording::create(path, width, height)?;
+        let writer = Writer::spawn(path, "recording", move |_, time, data| {
+            recording.record(time, data)
+        });
+        self.inner.state.lock().unwrap().recording = Some(writer);
+        Ok(())
+    }
+
+    pub fn stop_recording(&self) {
+        self.inner.state.lock().unwrap().recording = None;
+    }
+
+    pub fn recording_path(&self) -> Option<PathBuf> {
+        let state = self.inner.state.lock().unwrap();
+        state.recording.as_ref().map(|r| r.path.clone())
+    }
+
+    /// Take everything received since the last call.
+    pub fn take_unread(&self) -> Vec<u8> {
+        let mut state = self.inner.state.lock().unwrap();
+        let start = state.scrollback.len() - state.unread;
+        state.unread = 0;
+        state.scrollback.range(start..).copied().collect()
+    }
+
+    /// A position to later take only newer output from, with `take_since`.
+    pub fn position(&self) -> u64 {
+        self.inner.state.lock().unwrap().pushed
+    }
+
+    /// Take what was pushed after `position` out of the scrollback, moving `position` past
+    /// it. Older unread output is left for the user.
+    pub fn take_since(&self, position: &mut u64) -> Vec<u8> {
+        let mut state = self.inner.state.lock().unwrap();
+        let len = state.scrollback.len();
+        let count = (state.pushed - *position).min(len as u64) as usize;
+        *position = state.pushed;
+        state.unread = state.unread.saturating_sub(count);
+        state.scrollback.drain(len - count..).collect()
+    }
+
+    pub fn unread(&self) -> usize {
+        self.inner.state.lock().unwrap().unread
+    }
+
+    pub fn close(&self) {
+        self.inner.state.lock().unwrap().is_closed = true;
+        self.inner.notify.notify_one();
+    }
+
+    pub fn reopen(&self) {
+        self.inner.state.lock().unwrap().is_closed = false;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.state.lock().unwrap().is_closed
+    }
+
+    /// Wait until data is pushed or the output is closed.
+    pub async fn changed(&self) {
+        self.inner.notify.notified().await;
+    }
+
+    /// Push everything read from `reader` in the background, closing on EOF.
+    pub fn spawn_reader<R>(&self, mut reader: R) -> JoinHandle<()>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let output = self.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0; 4096];
+            while let Ok(n @ 1..) = reader.read(&mut buf).await {
+                output.push(&buf[..n]);
+            }
+            output.close();
+        })
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use miette::{bail, miette, IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    io::{stdout, AsyncWriteExt},
+    select,
+    signal::unix::{signal, SignalKind},
+    time::sleep,
+};
+
+use crate::terminal::SignalForwarding;
+
+/// A session's output stream, captured in asciicast v2 format.
+pub struct Recording {
+    file: BufWriter<File>,
+    start: Instant,
+    /// Trailing bytes of an incomplete UTF-8 sequence, held until the rest arrives.
+    pending: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u8,
+    width: u16,
+    height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+}
+
+impl Recording {
+    pub fn create(path: &Path, width: u16, height: u16) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs());
+        let header = Header {
+            version: 2,
+            width,
+            height,
+            timestamp,
+        };
+        serde_json::to_writer(&mut file, &header)?;
+        writeln!(file)?;
+        file.flush()?;
+
+        Ok(Self {
+            file,
+            start: Instant::now(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Add `data`, received at `time`.
+    pub fn record(&mut self, time: Instant, data: &[u8]) -> io::Result<()> {
+        self.pending.extend_from_slice(data);
+        let len = match std::str::from_utf8(&self.pending) {
+            Ok(s) => s.len(),
+            // Only hold back a sequence that may still be completed.
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+        if len == 0 {
+            return Ok(());
+        }
+
+        let data: Vec<u8> = self.pending.drain(..len).collect();
+        let event = (
+            time.duration_since(self.start).as_secs_f64(),
+            "o",
+            String::from_utf8_lossy(&data),
+        );
+        serde_json::to_writer(&mut self.file, &event)?;
+        writeln!(self.file)?;
+        self.file.flush()
+    }
+}
+
+/// Play the recording at `path` back on stdout until it ends or Ctrl-C is pressed.
+pub async fn replay(path: &Path, speed: f64) -> Result<()> {
+    if speed <= 0.0 || !speed.is_finite() {
+        bail!("Speed must be a positive number.");
+    }
+
+    let file = File::open(path).into_diagnostic()?;
+    let mut lines = BufReader::new(file).lines();
+
+    let header = lines
+        .next()
+        .ok_or_else(|| miette!("`{}` is empty.", path.display()))?
+        .into_diagnostic()?;
+    let header: Header = serde_json::from_str(&header)
+        .map_err(|e| miette!("`{}` is not an asciicast file: {e}", path.display()))?;
+    if header.version != 2 {
+        bail!("Unsupported asciicast version {}.", header.version);
+    }
+
+    let mut interrupt = signal(SignalKind::interrupt()).into_diagnostic()?;
+    // Detaching from a session leaves Ctrl-C with its default handler, which would kill rally.
+    let _forwarding = SignalForwarding::new()?;
+    let mut stdout = stdout();
+    let mut elapsed = 0.0;
+    for line in lines {
+        let line = line.into_diagnostic()?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (time, kind, data): (f64, String, Value) =
+            serde_json::from_str(&line).into_diagnostic()?;
+        if kind != "o" {
+            continue;
+        }
+        let Value::String(data) = data else {
+            continue;
+        };
+
+        let delay = ((time - elapsed) / speed).max(0.0);
+        elapsed = time;
+        select! {
+            () = sleep(Duration::from_secs_f64(delay)) => {}
+            _ = interrupt.recv() => break,
+        }
+
+        stdout.write_all(data.as_bytes()).await.into_diagnostic()?;
+        stdout.flush().await.into_diagnostic()?;
+    }
+
+    println!();
+    Ok(())
+}
@@ -6,6 +6,14 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub enum Scheme {
     Ssh,
+    Tcp,
+    Tls,
+    Ws,
+    Telnet,
+    Http,
+    Udp,
+    Unix,
+    Proc,
 }
 
 impl FromStr for Scheme {
@@ -14,6 +22,14 @@ impl FromStr for Scheme {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
             "ssh" => Self::Ssh,
+            "tcp" => Self::Tcp,
+            "tls" | "ssl" => Self::Tls,
+            "ws" | "wss" => Self::Ws,
+            "telnet" => Self::Telnet,
+            "http" | "https" => Self::Http,
+            "udp" => Self::Udp,
+            "unix" => Self::Unix,
+            "proc" | "exec" => Self::Proc,
             _ => bail!("Scheme {} is not supported.", s),
         })
     }
@@ -0,0 +1,66 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use miette::{bail, miette, Result};
+use tokio::time;
+
+use super::Session;
+
+/// How long to wait for a remote command to produce more output.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run `command` through the session's shell, capturing its output between markers.
+///
+/// The markers are split with `''` in the command line so that a shell echoing
+/// its input doesn't produce them early.
+pub async fn exec<S>(session: &mut S, command: &str) -> Result<Vec<u8>>
+where
+    S: Session + Send + ?Sized,
+{
+    let nonce = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let start = format!("RALLY_{nonce}_START");
+    let end = format!("RALLY_{nonce}_END");
+    let line = format!("echo RALLY''_{nonce}_START; {command}; echo RALLY''_{nonce}_END\n");
+
+    // Only what follows is taken, so output the user hasn't seen yet stays unread.
+    let mut position = session.output().position();
+    session.send(line.as_bytes()).await?;
+
+    let mut buf = Vec::new();
+    loop {
+        buf.extend(session.output().take_since(&mut position));
+        if let Some(out) = between(&buf, start.as_bytes(), end.as_bytes()) {
+            return Ok(out.to_vec());
+        }
+
+        if session.output().is_closed() {
+            bail!("Session closed while running `{}`.", command);
+        }
+
+        time::timeout(TIMEOUT, session.output().changed())
+            .await
+            .map_err(|_| miette!("Timed out while running `{}`.", command))?;
+    }
+}
+
+/// Quote `s` for a POSIX shell.
+pub fn quote(s: &str) -> Result<String> {
+    shlex::try_quote(s)
+        .map(Into::into)
+        .map_err(|e| miette!("Failed to quote `{}`: {}", s, e))
+}
+
+/// The lines after the `start` marker line and before the `end` marker.
+fn between<'a>(buf: &'a [u8], start: &[u8], end: &[u8]) -> Option<&'a [u8]> {
+    let from = find(buf, start)? + start.len();
+    let from = from + buf[from..].iter().position(|&b| b == b'\n')? + 1;
+    let to = from + find(&buf[from..], end)?;
+    Some(&buf[from..to])
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -18,12 +18,22 @@ pub struct StoredSession {
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct Sessions {
+    #[serde(default)]
     sessions: IndexMap<String, DeserializedSession>,
 }
 
 impl Sessions {
+    /// The ID the next added session will get.
+    pub fn next_id(&self) -> String {
+        // IDs of removed sessions are reused, so the count may already be taken.
+        (0..)
+            .map(|n: usize| n.to_string())
+            .find(|id| !self.sessions.contains_key(id))
+            .unwrap()
+    }
+
     pub fn add(&mut self, session: StoredSession) -> &mut StoredSession {
-        let id = self.sessions.len().to_string();
+        let id = self.next_id();
         self.sessions
             .insert(id.clone(), DeserializedSession::Initialized(session));
         let session = self.sessions.get_mut(&id).unwrap();
@@ -69,26 +79,104 @@ impl Sessions {
 
     pub async fn table(&mut self) -> Table {
         let mut builder = Builder::default();
-        builder.push_record(["ID", "URL", "Status"]);
+        builder.push_record(["ID", "URL", "Status", "Unread"]);
         for (id, session) in &mut self.sessions {
-            let (url, status) = match session {
+            let (url, status, unread) = match session {
                 DeserializedSession::Initialized(session) => {
                     let status = if session.is_connected().await {
                         "Connected"
                     } else {
                         "Disconnected"
                     };
-                    (session.connection_info.url.to_string(), status)
+                    let unread = session.output().unread();
+                    (session.connection_info.url.to_string(), status, unread)
                 }
                 DeserializedSession::Uninitialized(ConnectionInfo { url, .. }) => {
-                    (url.to_string(), "Disconnected")
+                    (url.to_string(), "Disconnected", 0)
                 }
             };
 
-            builder.push_record([id.to_owned(), url, status.to_string()]);
+            builder.push_record([id.to_owned(), url, status.to_string(), unread.to_string()]);
         }
         builder.build()
     }
+
+    /// A table of the forwards running on every session.
+    pub fn forwards_table(&self) -> Option<Table> {
+        let mut builder = Builder::default();
+        builder.push_record(["ID", "Session", "Type", "Listen", "Target"]);
+        let mut is_empty = true;
+        for (session_id, session) in &self.sessions {
+            let DeserializedSession::Initialized(session) = session else {
+                continue;
+            };
+            for (id, forward) in session.forwards() {
+                builder.push_record([
+                    id,
+                    session_id.to_owned(),
+                    forward.kind.to_string(),
+                    forward.spec.bind(),
+                    forward.spec.target(),
+                ]);
+                is_empty = false;
+            }
+        }
+        (!is_empty).then(|| builder.build())
+    }
+
+    /// Stop the forward `id`, on whichever session runs it.
+    pub async fn stop_forward(&mut self, id: &str) -> Result<()> {
+        for session in self.sessions.values_mut() {
+            if let DeserializedSession::Initialized(session) = session {
+                if session.stop_forward(id).await {
+                    return Ok(());
+                }
+            }
+        }
+        bail!("No forward found with ID `{}`.", id)
+    }
+
+    /// A two-column table describing the session `id`.
+    pub async fn info<K>(&mut self, id: &K) -> Result<Table>
+    where
+        K: AsRef<str> + Send + Sync,
+    {
+        let id = id.as_ref();
+        let session = self
+            .sessions
+            .get_mut(id)
+            .ok_or_else(|| miette!("No session found with ID `{}`.", id))?;
+
+        let mut builder = Builder::default();
+        builder.push_record(["ID", id]);
+        match session {
+            DeserializedSession::Initialized(session) => {
+                let status = if session.is_connected().await {
+                    "Connected"
+                } else {
+                    "Disconnected"
+                };
+                let output = session.output();
+                builder.push_record(["URL".to_string(), session.connection_info.url.to_string()]);
+                builder.push_record(["Status", status]);
+                builder.push_record(["Unread".to_string(), output.unread().to_string()]);
+                if let Some(path) = output.transcript_path() {
+                    builder.push_record(["Transcript".to_string(), path.display().to_string()]);
+                }
+                if let Some(path) = output.recording_path() {
+                    builder.push_record(["Recording".to_string(), path.display().to_string()]);
+                }
+                for (key, value) in session.details() {
+                    builder.push_record([key.to_string(), value]);
+                }
+            }
+            DeserializedSession::Uninitialized(ConnectionInfo { url, .. }) => {
+                builder.push_record(["URL".to_string(), url.to_string()]);
+                builder.push_record(["Status", "Disconnected"]);
+            }
+        }
+        Ok(builder.build())
+    }
 }
 
 impl Deref for StoredSession {
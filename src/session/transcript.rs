@@ -0,0 +1,119 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDateTime};
+use miette::{miette, Context, IntoDiagnostic, Result};
+use url::Url;
+
+use super::output::Output;
+use crate::history::{get_transcript_dir, target_name};
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// A timestamped, line-based log of a session's traffic.
+///
+/// Input lines are prefixed with `>` and output lines with `<`.
+pub struct Transcript {
+    file: BufWriter<File>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+impl Transcript {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: BufWriter::new(file),
+            input: Vec::new(),
+            output: Vec::new(),
+        })
+    }
+
+    pub fn record(&mut self, direction: Direction, data: &[u8]) -> io::Result<()> {
+        let (marker, line) = match direction {
+            Direction::Input => ('>', &mut self.input),
+            Direction::Output => ('<', &mut self.output),
+        };
+
+        for &b in data {
+            if b == b'\r' || b == b'\n' {
+                if !line.is_empty() {
+                    write_line(&mut self.file, marker, line)?;
+                    line.clear();
+                }
+            } else {
+                line.push(b);
+            }
+        }
+
+        self.file.flush()
+    }
+
+    /// Write out partial lines.
+    pub fn finish(&mut self) -> io::Result<()> {
+        for (marker, line) in [('>', &mut self.input), ('<', &mut self.output)] {
+            if !line.is_empty() {
+                write_line(&mut self.file, marker, line)?;
+                line.clear();
+            }
+        }
+
+        self.file.flush()
+    }
+}
+
+impl Drop for Transcript {
+    fn drop(&mut self) {
+        self.finish().ok();
+    }
+}
+
+/// Start logging `output` to a fresh transcript for `url`.
+pub fn start(output: &Output, url: &Url) -> Result<PathBuf> {
+    let path = new_path(url).ok_or_else(|| miette!("No data directory for transcripts."))?;
+    output
+        .start_transcript(&path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Failed to open transcript `{}`", path.display()))?;
+    Ok(path)
+}
+
+/// A fresh transcript path for a session connected to `url`.
+fn new_path(url: &Url) -> Option<PathBuf> {
+    let timestamp = Local::now().format(TIMESTAMP_FORMAT);
+    let name = format!("{}_{timestamp}.log", target_name(url));
+    get_transcript_dir().map(|dir| dir.join(name))
+}
+
+/// The most recent transcript recorded for `url`, if any.
+pub fn latest(url: &Url) -> Option<PathBuf> {
+    let prefix = format!("{}_", target_name(url));
+    fs::read_dir(get_transcript_dir()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix)?.strip_suffix(".log"))
+                // Another target's name can start with this one's, but not with a timestamp.
+                .is_some_and(|rest| NaiveDateTime::parse_from_str(rest, TIMESTAMP_FORMAT).is_ok())
+        })
+        .max()
+}
+
+fn write_line(file: &mut impl Write, marker: char, line: &[u8]) -> io::Result<()> {
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+    let line = String::from_utf8_lossy(line);
+    writeln!(file, "[{timestamp}] {marker} {line}")
+}
@@ -1,39 +1,135 @@
-use async_trait::async_trait;
-use clap::Subcommand;
-use miette::{IntoDiagnostic, Result};
-use tokio::sync::mpsc;
+pub mod sftp;
+mod transfer;
+mod upgrade;
+
+use std::path::PathBuf;
 
-use crate::repl::Repl;
+use async_trait::async_trait;
+use chrono::Local;
+use clap::{Subcommand, ValueEnum};
+use crossterm::terminal;
+use miette::{bail, IntoDiagnostic, Result};
 
-type MessageSender = mpsc::Sender<Option<Box<[u8]>>>;
+use crate::{
+    history::get_loot_dir,
+    repl::Repl,
+    session::{
+        forward::{Kind, Spec},
+        transcript, Session,
+    },
+};
 
-pub struct Termcraft {
-    tx: MessageSender,
+pub struct Termcraft<'a, S: ?Sized> {
+    session: &'a mut S,
 }
 
-impl Termcraft {
-    pub const fn new(tx: MessageSender) -> Self {
-        Self { tx }
+impl<'a, S: ?Sized> Termcraft<'a, S> {
+    pub fn new(session: &'a mut S) -> Self {
+        Self { session }
     }
 }
 
 #[async_trait]
-impl Repl for Termcraft {
+impl<S> Repl for Termcraft<'_, S>
+where
+    S: Session + Send + ?Sized,
+{
     type Commands = Commands;
 
     fn prompt(&self) -> &str {
-        ""
+        "termcraft"
     }
 
     async fn respond(&mut self, command: Self::Commands) -> Result<bool> {
         match command {
-            Commands::Bg => Ok(true),
+            Commands::Bg => return Ok(true),
             Commands::Echo { msg } => {
-                let msg = (msg.join(" ") + "\n").into_bytes().into_boxed_slice();
-                self.tx.send(Some(msg)).await.into_diagnostic()?;
-                Ok(false)
+                let msg = msg.join(" ") + "\n";
+                self.session.send(msg.as_bytes()).await?;
+            }
+            Commands::Upload { local, remote } => {
+                transfer::upload(self.session, &local, remote).await?;
+            }
+            Commands::Download { remote, local } => {
+                transfer::download(self.session, &remote, local).await?;
+            }
+            Commands::Sftp => {
+                let Some(sftp) = self.session.sftp().await? else {
+                    bail!("This session doesn't support SFTP.");
+                };
+                let url = self.session.url().clone();
+                sftp::Browser::new(sftp, url).await?.start().await?;
+            }
+            Commands::Upgrade => upgrade::upgrade(self.session).await?,
+            Commands::Log { state } => self.handle_log(state)?,
+            Commands::Record { state, file } => self.handle_record(state, file)?,
+            Commands::Hexdump { state } => {
+                self.session.set_hexdump(matches!(state, Toggle::On))?;
+            }
+            Commands::Forward { kind, spec } => self.handle_forward(kind, &spec).await?,
+            Commands::Socks { spec } => self.handle_forward(Kind::Dynamic, &spec).await?,
+        }
+        Ok(false)
+    }
+}
+
+impl<S> Termcraft<'_, S>
+where
+    S: Session + Send + ?Sized,
+{
+    async fn handle_forward(&mut self, kind: Kind, spec: &str) -> Result<()> {
+        let spec = Spec::parse(kind, spec)?;
+        let (id, forward) = self.session.forward(kind, spec).await?;
+        println!("Forwarding {forward} (forward `{id}`).");
+        Ok(())
+    }
+
+    fn handle_log(&self, state: Toggle) -> Result<()> {
+        let output = self.session.output();
+        match (state, output.transcript_path()) {
+            (Toggle::On, Some(path)) => {
+                println!("Already logging to {}.", path.display());
+            }
+            (Toggle::On, None) => {
+                let path = transcript::start(output, self.session.url())?;
+                println!("Logging to {}.", path.display());
+            }
+            (Toggle::Off, Some(path)) => {
+                output.stop_transcript();
+                println!("Stopped logging to {}.", path.display());
+            }
+            (Toggle::Off, None) => println!("Not logging."),
+        }
+        Ok(())
+    }
+
+    fn handle_record(&self, state: Toggle, file: Option<PathBuf>) -> Result<()> {
+        let output = self.session.output();
+        match (state, output.recording_path()) {
+            (Toggle::On, Some(path)) => {
+                println!("Already recording to {}.", path.display());
+            }
+            (Toggle::On, None) => {
+                let path = file.unwrap_or_else(|| {
+                    let timestamp = Local::now().format("%Y%m%d-%H%M%S");
+                    get_loot_dir(self.session.url()).join(format!("{timestamp}.cast"))
+                });
+                let (width, height) = terminal::size()
+                    .ok()
+                    .filter(|&(w, h)| w > 0 && h > 0)
+                    .unwrap_or((80, 24));
+                output
+                    .start_recording(&path, width, height)
+                    .into_diagnostic()?;
+                println!("Recording to {}.", path.display());
+            }
+            (Toggle::Off, Some(path)) => {
+                output.stop_recording();
+                println!("Saved recording to {}.", path.display());
             }
+            (Toggle::Off, None) => println!("Not recording."),
         }
+        Ok(())
     }
 }
 
@@ -48,4 +144,76 @@ pub enum Commands {
         #[arg(trailing_var_arg = true)]
         msg: Vec<String>,
     },
+
+    /// Upload a local file to the remote.
+    #[command(alias = "put")]
+    Upload {
+        /// Local file to upload.
+        local: PathBuf,
+
+        /// Remote destination (defaults to the file name).
+        remote: Option<String>,
+    },
+
+    /// Download a remote file into the session's loot directory.
+    #[command(alias = "get")]
+    Download {
+        /// Remote file to download.
+        remote: String,
+
+        /// Local destination (defaults to `loot/<target>/<file name>`).
+        local: Option<PathBuf>,
+    },
+
+    /// Browse the remote filesystem over SFTP.
+    Sftp,
+
+    /// Upgrade a raw shell to a full pty.
+    Upgrade,
+
+    /// Turn the session transcript on or off.
+    Log {
+        #[arg(value_enum)]
+        state: Toggle,
+    },
+
+    /// Start or stop an asciicast recording of the session output.
+    #[command(alias = "rec")]
+    Record {
+        #[arg(value_enum)]
+        state: Toggle,
+
+        /// File to record to (defaults to `loot/<target>/<timestamp>.cast`).
+        file: Option<PathBuf>,
+    },
+
+    /// Show received data as a hexdump.
+    #[command(alias = "hex")]
+    Hexdump {
+        #[arg(value_enum)]
+        state: Toggle,
+    },
+
+    /// Forward a port through the session, like `ssh -L`, `ssh -R` or `ssh -D`.
+    #[command(alias = "fwd")]
+    Forward {
+        /// L to listen locally, R to listen on the remote, D for a local SOCKS5 proxy.
+        #[arg(value_enum)]
+        kind: Kind,
+
+        /// Addresses, as `[bind_address:]port:host:hostport` (`[bind_address:]port` for D).
+        spec: String,
+    },
+
+    /// Run a SOCKS5 proxy that connects out from the remote, like `ssh -D`.
+    Socks {
+        /// Address to listen on, as `[bind_address:]port`.
+        spec: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Toggle {
+    On,
+    Off,
 }
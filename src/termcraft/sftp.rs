@@ -0,0 +1,545 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use clap::Subcommand;
+use miette::{bail, miette, IntoDiagnostic, Result, WrapErr};
+use russh_sftp::client::{fs::Metadata, SftpSession};
+use rustyline::{
+    completion::{Completer, FilenameCompleter, Pair},
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::Validator,
+    Context, Helper,
+};
+use tabled::builder::Builder;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+    runtime::Handle,
+    task::block_in_place,
+};
+use url::Url;
+
+use super::transfer::{progress_bar, SFTP_CHUNK_SIZE};
+use crate::{
+    history::get_loot_dir,
+    repl::{read_line_with, Repl},
+    style::Style,
+};
+
+/// File type bits of a mode, as in `st_mode`.
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFREG: u32 = 0o100000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFBLK: u32 = 0o060000;
+const S_IFIFO: u32 = 0o010000;
+const S_IFSOCK: u32 = 0o140000;
+
+/// Browses the remote filesystem over an SFTP channel.
+pub struct Browser {
+    sftp: Arc<SftpSession>,
+    /// Where `cd` without a path goes.
+    home: String,
+    cwd: String,
+    /// URL of the session, naming the loot directory for downloads.
+    url: Url,
+}
+
+impl Browser {
+    pub async fn new(sftp: SftpSession, url: Url) -> Result<Self> {
+        let home = sftp.canonicalize(".").await.into_diagnostic()?;
+        Ok(Self {
+            sftp: Arc::new(sftp),
+            cwd: home.clone(),
+            home,
+            url,
+        })
+    }
+
+    /// `path` relative to the working directory, with `~` as the home directory.
+    fn resolve(&self, path: &str) -> String {
+        match path.strip_prefix('~') {
+            Some("") => self.home.clone(),
+            Some(rest) if rest.starts_with('/') => join(&self.home, &rest[1..]),
+            _ => join(&self.cwd, path),
+        }
+    }
+
+    async fn list(&self, path: Option<String>) -> Result<()> {
+        let path = self.resolve(path.as_deref().unwrap_or("."));
+        let metadata = self
+            .sftp
+            .metadata(&path)
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to stat `{path}`"))?;
+
+        let mut entries: Vec<_> = if file_type(&metadata) == S_IFDIR {
+            self.sftp
+                .read_dir(&path)
+                .await
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Failed to list `{path}`"))?
+                .map(|entry| (entry.file_name(), entry.metadata()))
+                .filter(|(name, _)| name != "." && name != "..")
+                .collect()
+        } else {
+            vec![(path.clone(), metadata)]
+        };
+        if entries.is_empty() {
+            println!("`{path}` is empty.");
+            return Ok(());
+        }
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut builder = Builder::default();
+        builder.push_record(["Mode", "Owner", "Size", "Modified", "Name"]);
+        for (name, metadata) in entries {
+            let mut name = name.escape_debug().to_string();
+            if file_type(&metadata) == S_IFDIR {
+                name.push('/');
+            }
+            builder.push_record([
+                mode(&metadata),
+                owner(metadata.user.as_ref(), metadata.uid),
+                metadata.len().to_string(),
+                time(metadata.mtime),
+                name,
+            ]);
+        }
+        println!("{}", builder.build().style());
+        Ok(())
+    }
+
+    async fn change_dir(&mut self, path: Option<String>) -> Result<()> {
+        let path = self.resolve(path.as_deref().unwrap_or("~"));
+        let path = self
+            .sftp
+            .canonicalize(&path)
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to resolve `{path}`"))?;
+        let metadata = self.sftp.metadata(&path).await.into_diagnostic()?;
+        if file_type(&metadata) != S_IFDIR {
+            bail!("`{}` is not a directory.", path);
+        }
+        self.cwd = path;
+        Ok(())
+    }
+
+    async fn get(&self, remote: &str, local: Option<PathBuf>) -> Result<()> {
+        let remote = self.resolve(remote);
+        let name = Path::new(&remote)
+            .file_name()
+            .ok_or_else(|| miette!("No file name in `{}`.", remote))?;
+        let local = match local {
+            Some(local) if local.is_dir() => local.join(name),
+            Some(local) => local,
+            None => get_loot_dir(&self.url).join(name),
+        };
+
+        let metadata = self
+            .sftp
+            .metadata(&remote)
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to stat `{remote}`"))?;
+        if file_type(&metadata) == S_IFDIR {
+            bail!("`{}` is a directory.", remote);
+        }
+
+        let bar = progress_bar(metadata.len() as usize);
+        let mut file = self.sftp.open(&remote).await.into_diagnostic()?;
+        let mut out = fs::File::create(&local).await.into_diagnostic()?;
+        let mut buf = vec![0; SFTP_CHUNK_SIZE];
+        loop {
+            let n = file.read(&mut buf).await.into_diagnostic()?;
+            if n == 0 {
+                break;
+            }
+            out.write_all(&buf[..n]).await.into_diagnostic()?;
+            bar.inc(n as u64);
+        }
+        out.flush().await.into_diagnostic()?;
+        bar.finish_and_clear();
+
+        println!("Downloaded {remote} to {}.", local.display());
+        Ok(())
+    }
+
+    async fn put(&self, local: &Path, remote: Option<String>) -> Result<()> {
+        let name = local
+            .file_name()
+            .ok_or_else(|| miette!("No file name in `{}`.", local.display()))?
+            .to_string_lossy();
+        let remote = self.resolve(remote.as_deref().unwrap_or(&name));
+        let remote = match self.sftp.metadata(&remote).await {
+            Ok(metadata) if file_type(&metadata) == S_IFDIR => join(&remote, &name),
+            _ => remote,
+        };
+
+        let mut data = fs::File::open(local).await.into_diagnostic()?;
+        let len = data.metadata().await.into_diagnostic()?.len();
+        let bar = progress_bar(len as usize);
+        let mut file = self
+            .sftp
+            .create(&remote)
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to create `{remote}`"))?;
+        let mut buf = vec![0; SFTP_CHUNK_SIZE];
+        loop {
+            let n = data.read(&mut buf).await.into_diagnostic()?;
+            if n == 0 {
+                break;
+            }
+            file.write_all(&buf[..n]).await.into_diagnostic()?;
+            bar.inc(n as u64);
+        }
+        file.shutdown().await.into_diagnostic()?;
+        bar.finish_and_clear();
+
+        println!("Uploaded {} to {remote}.", local.display());
+        Ok(())
+    }
+
+    async fn stat(&self, path: &str) -> Result<()> {
+        let path = self.resolve(path);
+        let metadata = self
+            .sftp
+            .symlink_metadata(&path)
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to stat `{path}`"))?;
+
+        let kind = match file_type(&metadata) {
+            S_IFDIR => "Directory",
+            S_IFLNK => "Symlink",
+            S_IFREG => "File",
+            S_IFCHR => "Character device",
+            S_IFBLK => "Block device",
+            S_IFIFO => "FIFO",
+            S_IFSOCK => "Socket",
+            _ => "Unknown",
+        };
+
+        let mut builder = Builder::default();
+        builder.push_record(["Path", &path]);
+        builder.push_record(["Type", kind]);
+        if file_type(&metadata) == S_IFLNK {
+            if let Ok(target) = self.sftp.read_link(&path).await {
+                builder.push_record(["Target".to_string(), target]);
+            }
+        }
+        builder.push_record(["Size".to_string(), metadata.len().to_string()]);
+        builder.push_record([
+            "Mode".to_string(),
+            format!(
+                "{} ({:04o})",
+                mode(&metadata),
+                metadata.permissions.unwrap_or(0) & 0o7777
+            ),
+        ]);
+        builder.push_record([
+            "Owner".to_string(),
+            owner(metadata.user.as_ref(), metadata.uid),
+        ]);
+        builder.push_record([
+            "Group".to_string(),
+            owner(metadata.group.as_ref(), metadata.gid),
+        ]);
+        builder.push_record(["Accessed".to_string(), time(metadata.atime)]);
+        builder.push_record(["Modified".to_string(), time(metadata.mtime)]);
+        println!("{}", builder.build().style());
+        Ok(())
+    }
+
+    async fn remove(&self, path: &str) -> Result<()> {
+        let path = self.resolve(path);
+        let metadata = self
+            .sftp
+            .symlink_metadata(&path)
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to stat `{path}`"))?;
+
+        let res = if file_type(&metadata) == S_IFDIR {
+            self.sftp.remove_dir(&path).await
+        } else {
+            self.sftp.remove_file(&path).await
+        };
+        res.into_diagnostic()
+            .wrap_err_with(|| format!("Failed to remove `{path}`"))
+    }
+}
+
+#[async_trait]
+impl Repl for Browser {
+    type Commands = Commands;
+
+    fn prompt(&self) -> &str {
+        "sftp"
+    }
+
+    fn read_input(&self) -> Result<String> {
+        let completion = Completion {
+            sftp: self.sftp.clone(),
+            cwd: self.cwd.clone(),
+            local: FilenameCompleter::new(),
+        };
+        read_line_with(self.prompt(), completion)
+    }
+
+    async fn respond(&mut self, command: Self::Commands) -> Result<bool> {
+        match command {
+            Commands::Exit => return Ok(true),
+            Commands::Ls { path } => self.list(path).await?,
+            Commands::Cd { path } => self.change_dir(path).await?,
+            Commands::Pwd => println!("{}", self.cwd),
+            Commands::Get { remote, local } => self.get(&remote, local).await?,
+            Commands::Put { local, remote } => self.put(&local, remote).await?,
+            Commands::Stat { path } => self.stat(&path).await?,
+            Commands::Rm { path } => self.remove(&path).await?,
+        }
+        Ok(false)
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Leave the SFTP browser.
+    #[command(aliases = ["bg", "quit"])]
+    Exit,
+
+    /// List a remote directory.
+    #[command(aliases = ["dir", "ll"])]
+    Ls {
+        /// Directory or file to list (defaults to the working directory).
+        path: Option<String>,
+    },
+
+    /// Change the remote working directory.
+    Cd {
+        /// Directory to change to (defaults to the home directory).
+        path: Option<String>,
+    },
+
+    /// Show the remote working directory.
+    Pwd,
+
+    /// Download a remote file.
+    #[command(alias = "download")]
+    Get {
+        /// Remote file to download.
+        remote: String,
+
+        /// Local destination (defaults to `loot/<target>/<file name>`).
+        local: Option<PathBuf>,
+    },
+
+    /// Upload a local file.
+    #[command(alias = "upload")]
+    Put {
+        /// Local file to upload.
+        local: PathBuf,
+
+        /// Remote destination (defaults to the file name in the working directory).
+        remote: Option<String>,
+    },
+
+    /// Show details about a remote file.
+    Stat {
+        /// Remote file, not followed if it's a symlink.
+        path: String,
+    },
+
+    /// Remove a remote file or empty directory.
+    #[command(alias = "del")]
+    Rm {
+        /// Remote file or directory.
+        path: String,
+    },
+}
+
+/// Completes command names, then remote paths, or local ones where a command takes them.
+struct Completion {
+    sftp: Arc<SftpSession>,
+    cwd: String,
+    local: FilenameCompleter,
+}
+
+impl Completion {
+    fn remote(&self, word: &str) -> Vec<Pair> {
+        let (dir, prefix) = match word.rfind('/') {
+            Some(i) => word.split_at(i + 1),
+            None => ("", word),
+        };
+
+        // Completion is synchronous, so block this thread on the listing.
+        let path = join(&self.cwd, if dir.is_empty() { "." } else { dir });
+        let Ok(entries) = block_in_place(|| Handle::current().block_on(self.sftp.read_dir(path)))
+        else {
+            return Vec::new();
+        };
+
+        let mut candidates: Vec<_> = entries
+            .filter(|entry| {
+                let name = entry.file_name();
+                // Like shells, only offer hidden files once a dot is typed.
+                name.starts_with(prefix)
+                    && (prefix.starts_with('.') || !name.starts_with('.'))
+                    && name != "."
+                    && name != ".."
+            })
+            .map(|entry| {
+                let mut name = entry.file_name();
+                if file_type(&entry.metadata()) == S_IFDIR {
+                    name.push('/');
+                }
+                Pair {
+                    replacement: escape(&format!("{dir}{name}")),
+                    display: name,
+                }
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates
+    }
+}
+
+impl Completer for Completion {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let words = split(&line[..pos]);
+        let (start, word) = words.last().cloned().unwrap_or_default();
+
+        let candidates = match (words[0].1.as_str(), words.len()) {
+            (_, 1) => Commands::augment_subcommands(clap::Command::new("sftp"))
+                .get_subcommands()
+                .map(|command| command.get_name().to_string())
+                .filter(|name| name.starts_with(&word))
+                .map(|name| Pair {
+                    display: name.clone(),
+                    replacement: name + " ",
+                })
+                .collect(),
+            ("put" | "upload", 2) | ("get" | "download", 3) => {
+                return self.local.complete(line, pos, ctx);
+            }
+            _ => self.remote(&word),
+        };
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Completion {
+    type Hint = String;
+}
+
+impl Highlighter for Completion {}
+
+impl Validator for Completion {}
+
+impl Helper for Completion {}
+
+/// Split `line` into words as `shlex` would, with where each starts. The last word is the
+/// one being typed, and may be empty.
+fn split(line: &str) -> Vec<(usize, String)> {
+    let mut words = vec![(0, String::new())];
+    let mut quote = None;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let (start, word) = words.last_mut().unwrap();
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '\\') => {
+                if let Some((_, c)) = chars.next() {
+                    word.push(c);
+                }
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => quote = Some(c),
+            (None, c) if c.is_whitespace() => {
+                let next = i + c.len_utf8();
+                if word.is_empty() {
+                    *start = next;
+                } else {
+                    words.push((next, String::new()));
+                }
+            }
+            (None, c) => word.push(c),
+        }
+    }
+    words
+}
+
+/// Escape `s` so `shlex` reads it back as one word.
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        if c.is_whitespace() || matches!(c, '\\' | '\'' | '"' | '#') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn join(dir: &str, path: &str) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else if dir.ends_with('/') {
+        format!("{dir}{path}")
+    } else {
+        format!("{dir}/{path}")
+    }
+}
+
+/// The file type bits of `metadata`'s mode, comparable to `S_IFDIR` and friends.
+fn file_type(metadata: &Metadata) -> u32 {
+    metadata.permissions.unwrap_or(0) & S_IFMT
+}
+
+/// The mode as `ls -l` shows it.
+fn mode(metadata: &Metadata) -> String {
+    let kind = match file_type(metadata) {
+        S_IFDIR => 'd',
+        S_IFLNK => 'l',
+        S_IFCHR => 'c',
+        S_IFBLK => 'b',
+        S_IFIFO => 'p',
+        S_IFSOCK => 's',
+        _ => '-',
+    };
+    format!("{kind}{}", metadata.permissions())
+}
+
+/// A user or group by name if the server sent one, otherwise by ID.
+fn owner(name: Option<&String>, id: Option<u32>) -> String {
+    match (name, id) {
+        (Some(name), _) => name.clone(),
+        (None, Some(id)) => id.to_string(),
+        (None, None) => "?".to_string(),
+    }
+}
+
+fn time(timestamp: Option<u32>) -> String {
+    timestamp
+        .and_then(|t| DateTime::from_timestamp(t.into(), 0))
+        .map_or_else(
+            || "?".to_string(),
+            |t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+        )
+}
@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use indicatif::{ProgressBar, ProgressStyle};
+use miette::{bail, miette, IntoDiagnostic, Result};
+use russh_sftp::client::SftpSession;
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+use crate::{
+    history::get_loot_dir,
+    session::{shell::quote, Session},
+};
+
+/// Bytes written per SFTP write.
+pub(super) const SFTP_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Bytes encoded per shell command, keeping lines under the usual 4096 byte tty limit.
+const SHELL_CHUNK_SIZE: usize = 2048;
+
+/// Bytes read per shell command when downloading, keeping the encoded output well
+/// within the session scrollback.
+const SHELL_READ_SIZE: usize = 16 * 1024;
+
+pub async fn upload<S>(session: &mut S, local: &Path, remote: Option<String>) -> Result<()>
+where
+    S: Session + Send + ?Sized,
+{
+    let data = fs::read(local).await.into_diagnostic()?;
+    let remote = match remote {
+        Some(remote) => remote,
+        None => local
+            .file_name()
+            .ok_or_else(|| miette!("No file name in `{}`.", local.display()))?
+            .to_string_lossy()
+            .into_owned(),
+    };
+
+    let bar = progress_bar(data.len());
+    if let Some(sftp) = sftp(session).await {
+        let mut file = sftp.create(remote.clone()).await.into_diagnostic()?;
+        for chunk in data.chunks(SFTP_CHUNK_SIZE) {
+            file.write_all(chunk).await.into_diagnostic()?;
+            bar.inc(chunk.len() as u64);
+        }
+        file.shutdown().await.into_diagnostic()?;
+    } else {
+        let path = quote(&remote)?;
+        let encoded = quote(&format!("{remote}.b64"))?;
+
+        session.exec(&format!(": > {encoded}")).await?;
+        for chunk in data.chunks(SHELL_CHUNK_SIZE) {
+            let command = format!("printf %s {} >> {encoded}", BASE64.encode(chunk));
+            session.exec(&command).await?;
+            bar.inc(chunk.len() as u64);
+        }
+        session
+            .exec(&format!("base64 -d {encoded} > {path}; rm -f {encoded}"))
+            .await?;
+    }
+    bar.finish_and_clear();
+
+    verify(session, &remote, &data).await?;
+    println!("Uploaded {} to {remote}.", local.display());
+    Ok(())
+}
+
+pub async fn download<S>(session: &mut S, remote: &str, local: Option<PathBuf>) -> Result<()>
+where
+    S: Session + Send + ?Sized,
+{
+    let name = Path::new(remote)
+        .file_name()
+        .ok_or_else(|| miette!("No file name in `{}`.", remote))?;
+    let local = match local {
+        Some(local) if local.is_dir() => local.join(name),
+        Some(local) => local,
+        None => get_loot_dir(session.url()).join(name),
+    };
+
+    let mut data = Vec::new();
+    if let Some(sftp) = sftp(session).await {
+        let len = sftp
+            .metadata(remote)
+            .await
+            .into_diagnostic()?
+            .size
+            .unwrap_or(0);
+        let bar = progress_bar(len as usize);
+
+        let mut file = sftp.open(remote).await.into_diagnostic()?;
+        let mut buf = vec![0; SFTP_CHUNK_SIZE];
+        loop {
+            let n = file.read(&mut buf).await.into_diagnostic()?;
+            if n == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..n]);
+            bar.inc(n as u64);
+        }
+        bar.finish_and_clear();
+    } else {
+        let path = quote(remote)?;
+        let out = session.exec(&format!("wc -c < {path}")).await?;
+        let len: usize = String::from_utf8_lossy(&out)
+            .trim()
+            .parse()
+            .map_err(|_| miette!("Failed to read the size of `{}`.", remote))?;
+        let bar = progress_bar(len);
+
+        for block in 0..len.div_ceil(SHELL_READ_SIZE) {
+            let command = format!(
+                "dd if={path} bs={SHELL_READ_SIZE} skip={block} count=1 2>/dev/null | base64"
+            );
+            let mut out = session.exec(&command).await?;
+            out.retain(|b| !b.is_ascii_whitespace());
+            let chunk = BASE64.decode(&out).into_diagnostic()?;
+            bar.inc(chunk.len() as u64);
+            data.extend(chunk);
+        }
+        bar.finish_and_clear();
+    }
+
+    verify(session, remote, &data).await?;
+    fs::write(&local, &data).await.into_diagnostic()?;
+    println!("Downloaded {remote} to {}.", local.display());
+    Ok(())
+}
+
+/// The session's SFTP subsystem, or `None` to transfer through its shell instead.
+async fn sftp<S>(session: &mut S) -> Option<SftpSession>
+where
+    S: Session + Send + ?Sized,
+{
+    match session.sftp().await {
+        Ok(sftp) => sftp,
+        Err(e) => {
+            eprintln!("{e}\nTransferring through the shell instead.");
+            None
+        }
+    }
+}
+
+/// Compare the SHA-256 of `remote` against `data`.
+async fn verify<S>(session: &mut S, remote: &str, data: &[u8]) -> Result<()>
+where
+    S: Session + Send + ?Sized,
+{
+    let path = quote(remote)?;
+    let out = session
+        .exec(&format!(
+            "sha256sum {path} 2>/dev/null || shasum -a 256 {path}"
+        ))
+        .await?;
+    let out = String::from_utf8_lossy(&out);
+    let remote_hash = out
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| miette!("Failed to hash `{}` on the remote.", remote))?;
+
+    let local_hash = format!("{:x}", Sha256::digest(data));
+    if remote_hash != local_hash {
+        bail!(
+            "Checksum mismatch for `{}`: expected {}, got {}.",
+            remote,
+            local_hash,
+            remote_hash
+        );
+    }
+
+    Ok(())
+}
+
+pub(super) fn progress_bar(len: usize) -> ProgressBar {
+    let bar = ProgressBar::new(len as u64);
+    if let Ok(style) = ProgressStyle::with_template("[{bar:40}] {bytes}/{total_bytes} ({eta})") {
+        bar.set_style(style.progress_chars("=> "));
+    }
+    bar
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use miette::{bail, IntoDiagnostic, Result};
+use tokio::time;
+
+use crate::session::Session;
+
+/// Time for the remote pty to spawn before configuring it.
+const SPAWN_DELAY: Duration = Duration::from_millis(500);
+
+pub async fn upgrade<S>(session: &mut S) -> Result<()>
+where
+    S: Session + Send + ?Sized,
+{
+    if session.has_pty() {
+        bail!("Session already has a pty.");
+    }
+    if !session.can_upgrade() {
+        bail!("This session can't be upgraded to a pty.");
+    }
+
+    // dash's `command -v` only resolves its first argument.
+    let out = session
+        .exec("for c in python3 python script socat bash; do command -v \"$c\"; done")
+        .await?;
+    let out = String::from_utf8_lossy(&out);
+    let find = |name: &str| {
+        out.lines()
+            .map(str::trim)
+            .find(|path| path.rsplit('/').next() == Some(name))
+    };
+
+    let shell = find("bash").unwrap_or("/bin/sh");
+    let command = if let Some(python) = find("python3").or_else(|| find("python")) {
+        format!("{python} -c 'import pty; pty.spawn(\"{shell}\")'")
+    } else if let Some(script) = find("script") {
+        format!("{script} -qc {shell} /dev/null")
+    } else if let Some(socat) = find("socat") {
+        format!("{socat} - exec:{shell},pty,stderr,setsid,sigint,sane")
+    } else {
+        bail!("No way to spawn a pty found (tried python3, python, script and socat).");
+    };
+
+    session.send(format!("{command}\n").as_bytes()).await?;
+    time::sleep(SPAWN_DELAY).await;
+
+    let term = std::env::var("TERM").unwrap_or_else(|_| "xterm".into());
+    let (cols, rows) = crossterm::terminal::size().into_diagnostic()?;
+    let setup = format!("export TERM={term}; stty rows {rows} cols {cols}\n");
+    session.send(setup.as_bytes()).await?;
+
+    session.set_pty();
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::io::{self, stdin, IsTerminal};
+use std::sync::Mutex;
+use std::{mem, ptr};
+
+use crossterm::terminal;
+use libc::c_int;
+use miette::{IntoDiagnostic, Result};
+
+/// Tracks whether the local terminal is in raw mode, restoring it when dropped.
+///
+/// Does nothing when stdin isn't a terminal.
+#[derive(Default)]
+pub struct RawMode {
+    is_enabled: bool,
+}
+
+impl RawMode {
+    /// Return to cooked mode, e.g. to read a command.
+    pub fn suspend(&mut self) -> Result<()> {
+        if self.is_enabled {
+            terminal::disable_raw_mode().into_diagnostic()?;
+            self.is_enabled = false;
+        }
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<()> {
+        if !self.is_enabled && stdin().is_terminal() {
+            terminal::enable_raw_mode().into_diagnostic()?;
+            self.is_enabled = true;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        self.suspend().ok();
+    }
+}
+
+/// The signals sent by the keys a session forwards while the terminal isn't raw.
+const SIGNALS: [c_int; 2] = [libc::SIGINT, libc::SIGTSTP];
+
+/// Tokio's handlers, put aside while the default ones are installed.
+static SAVED_ACTIONS: Mutex<Vec<(c_int, libc::sigaction)>> = Mutex::new(Vec::new());
+
+/// Sends Ctrl-C and Ctrl-Z to tokio's signal listeners, restoring the default handlers
+/// when dropped.
+///
+/// Tokio never uninstalls its handlers, so without this the keys would be swallowed for
+/// the rest of the process once a session had been attached. Create it right after the
+/// listeners.
+pub struct SignalForwarding(());
+
+impl SignalForwarding {
+    pub fn new() -> Result<Self> {
+        let mut forwarding = Self(());
+        forwarding.forward()?;
+        Ok(forwarding)
+    }
+
+    /// Give the signals back their default handlers, e.g. to read a command.
+    pub fn restore(&mut self) -> Result<()> {
+        let mut saved = SAVED_ACTIONS.lock().unwrap();
+        if saved.is_empty() {
+            for signal in SIGNALS {
+                saved.push((signal, set_handler(signal, libc::SIG_DFL)?));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reinstall the handlers put aside by `restore`.
+    pub fn forward(&mut self) -> Result<()> {
+        for (signal, action) in SAVED_ACTIONS.lock().unwrap().drain(..) {
+            set_action(signal, &action)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SignalForwarding {
+    fn drop(&mut self) {
+        self.restore().ok();
+    }
+}
+
+/// Ignores Ctrl-C and Ctrl-\ until dropped, for while a child in the foreground, like a
+/// pager, handles them itself.
+pub struct IgnoreInterrupts {
+    saved: Vec<(c_int, libc::sigaction)>,
+}
+
+impl IgnoreInterrupts {
+    pub fn new() -> Result<Self> {
+        let saved = [libc::SIGINT, libc::SIGQUIT]
+            .into_iter()
+            .map(|signal| Ok((signal, set_handler(signal, libc::SIG_IGN)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for IgnoreInterrupts {
+    fn drop(&mut self) {
+        for (signal, action) in &self.saved {
+            set_action(*signal, action).ok();
+        }
+    }
+}
+
+/// Set `signal`'s disposition to `handler`, returning the action it replaces.
+fn set_handler(signal: c_int, handler: libc::sighandler_t) -> Result<libc::sigaction> {
+    // SAFETY: an all-zero `sigaction` is valid, with no flags and an empty mask.
+    let mut action: libc::sigaction = unsafe { mem::zeroed() };
+    action.sa_sigaction = handler;
+    let mut old = unsafe { mem::zeroed() };
+    if unsafe { libc::sigaction(signal, &action, &mut old) } == -1 {
+        return Err(io::Error::last_os_error()).into_diagnostic();
+    }
+    Ok(old)
+}
+
+fn set_action(signal: c_int, action: &libc::sigaction) -> Result<()> {
+    if unsafe { libc::sigaction(signal, action, ptr::null_mut()) } == -1 {
+        return Err(io::Error::last_os_error()).into_diagnostic();
+    }
+    Ok(())
+}